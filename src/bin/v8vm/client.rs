@@ -0,0 +1,46 @@
+use std::thread::spawn;
+use anyhow::Result;
+use reqwest::blocking::Client;
+use v8vm::ex::fetch::{self, Request, Response};
+use v8vm::vm::Resolver;
+
+pub struct HttpClient {
+    client: Client,
+}
+
+impl HttpClient {
+    pub fn new() -> Self {
+        let client = Client::new();
+        Self { client }
+    }
+
+    fn send(client: &Client, request: Request) -> Result<Response> {
+        let method  = request.method.parse()?;
+        let url     = request.url.parse()?;
+        let headers = request.headers;
+
+        let mut request = reqwest::blocking::Request::new(method, url);
+        *request.headers_mut() = headers;
+
+        let response = client.execute(request)?;
+        let status   = response.status();
+        let body     = response.text()?;
+
+        Ok(Response {
+            status: status,
+            body:   body,
+        })
+    }
+}
+
+impl fetch::Client for HttpClient {
+    fn fetch(&self, request: Request, resolver: Resolver) {
+        let client = self.client.clone();
+        spawn(move || {
+            let _ = match Self::send(&client, request) {
+                Ok(r)  => resolver.resolve(Box::new(r)),
+                Err(e) => resolver.reject(Box::new(e)),
+            };
+        });
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use v8::{new_default_platform, V8};
+use v8vm::vm::Failure;
+
+mod client;
+mod repl;
+mod run;
+
+fn main() -> Result<()> {
+    let platform = new_default_platform(0, false).make_shared();
+    V8::initialize_platform(platform);
+    V8::initialize();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args.first().map(String::as_str) {
+        None | Some("repl")            => repl::run(),
+        Some("run")                    => run::run(&args[1..]),
+        Some("help" | "-h" | "--help") => {
+            println!("{HELP}\n{}", run::USAGE);
+            Ok(())
+        },
+        Some(command)                  => Err(anyhow!("unknown command {command}")),
+    }
+}
+
+fn print(result: Result<Value>) {
+    match result {
+        Ok(value) => match serde_json::to_string_pretty(&value) {
+            Ok(json) => println!("{json}"),
+            Err(e)   => eprintln!("error: {e}"),
+        },
+        Err(e) => match e.downcast_ref::<Failure>() {
+            Some(failure) => eprintln!("{failure}"),
+            None          => eprintln!("error: {e:?}"),
+        },
+    }
+}
+
+const HELP: &str = "\
+usage: v8vm [repl]
+
+input that uses top-level await runs inside an async function, its
+top-level declarations are copied onto globalThis once it finishes
+";
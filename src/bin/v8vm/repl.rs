@@ -0,0 +1,155 @@
+use anyhow::{Error, Result};
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use serde_json::Value;
+use v8vm::{Handle, Machine, ex::Fetch};
+use v8vm::vm::Failure;
+use super::client::HttpClient;
+use super::print;
+
+pub fn run() -> Result<()> {
+    let mut machine = Machine::new(String::new());
+    machine.name("<repl>".to_owned());
+    machine.extend(Fetch::new(HttpClient::new()));
+
+    let (handle, _guard) = machine.exec();
+
+    let mut editor = DefaultEditor::new()?;
+    let mut buffer = String::new();
+
+    loop {
+        let prompt = match buffer.is_empty() {
+            true  => "> ",
+            false => "... ",
+        };
+
+        match editor.readline(prompt) {
+            Ok(line)                        => buffer.push_str(&line),
+            Err(ReadlineError::Interrupted) => {
+                buffer.clear();
+                continue;
+            },
+            Err(ReadlineError::Eof)         => break,
+            Err(e)                          => return Err(e.into()),
+        }
+        buffer.push('\n');
+
+        // input is compiled before it runs and never run twice
+        let result = match form(&handle, &buffer) {
+            Ok(Some(form)) => eval(&handle, &buffer, form),
+            Ok(None)       => continue,
+            Err(e)         => Err(e),
+        };
+
+        editor.add_history_entry(buffer.trim_end())?;
+        buffer.clear();
+
+        print(result);
+    }
+
+    Ok(())
+}
+
+enum Form {
+    Script,
+    Expression,
+    Statements,
+}
+
+// compile the input inside functions that are never called to pick how it
+// runs, top-level await needs an async expression or async statements
+fn form(handle: &Handle, source: &str) -> Result<Option<Form>> {
+    let script = format!("void function () {{\n{source}\n}}");
+    match handle.eval(&script) {
+        Ok(_)                                 => return Ok(Some(Form::Script)),
+        Err(e) if message(&e).contains(AWAIT) => (),
+        Err(e)                                => return incomplete(e, &script),
+    }
+
+    if handle.eval(&format!("void (async () => (\n{source}\n))")).is_ok() {
+        return Ok(Some(Form::Expression));
+    }
+
+    let body = format!("void (async () => {{\n{source}\n}})");
+    match handle.eval(&body) {
+        Ok(_)  => Ok(Some(Form::Statements)),
+        Err(e) => incomplete(e, &body),
+    }
+}
+
+// input that breaks off runs into the wrapper's closing line, wait for more
+fn incomplete(error: Error, wrapper: &str) -> Result<Option<Form>> {
+    let last = wrapper.lines().count();
+    let line = error.downcast_ref::<Failure>().and_then(|f| f.location.as_ref()).map(|l| l.line);
+    let open = INCOMPLETE.iter().any(|incomplete| message(&error).contains(incomplete));
+
+    match open || line.map_or(false, |line| line >= last) {
+        true  => Ok(None),
+        false => Err(error),
+    }
+}
+
+fn eval(handle: &Handle, source: &str, form: Form) -> Result<Value> {
+    match form {
+        Form::Script     => handle.eval(source),
+        Form::Expression => handle.eval(&format!("(async () => (\n{source}\n))()")),
+        Form::Statements => {
+            let hoist = hoist(source);
+            handle.eval(&format!("(async () => {{\n{source}\n;{hoist}\n}})()"))
+        },
+    }
+}
+
+// copy the wrapper's top-level declarations onto globalThis so later input
+// sees them, names that are not declared there are skipped or left as is
+fn hoist(source: &str) -> String {
+    let mut names = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if !(c.is_ascii_alphanumeric() || c == '_' || c == '$') {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        while let Some(&(at, c)) = chars.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '$') {
+                break;
+            }
+            end = at + c.len_utf8();
+            chars.next();
+        }
+
+        let name = &source[start..end];
+        if !c.is_ascii_digit() && !RESERVED.contains(&name) && !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    names.iter().map(|name| {
+        format!("try {{ globalThis.{name} = {name}; }} catch {{}}")
+    }).collect::<Vec<_>>().join(" ")
+}
+
+fn message(error: &Error) -> &str {
+    match error.downcast_ref::<Failure>() {
+        Some(failure) => &failure.message,
+        None          => "",
+    }
+}
+
+const AWAIT: &str = "await is only valid";
+
+const RESERVED: &[&str] = &[
+    "arguments", "await", "break", "case", "catch", "class", "const", "continue",
+    "debugger", "default", "delete", "do", "else", "enum", "eval", "export",
+    "extends", "false", "finally", "for", "function", "if", "implements", "import",
+    "in", "instanceof", "interface", "let", "new", "null", "package", "private",
+    "protected", "public", "return", "static", "super", "switch", "this", "throw",
+    "true", "try", "typeof", "var", "void", "while", "with", "yield",
+];
+
+const INCOMPLETE: &[&str] = &[
+    "Unexpected end of input",
+    "Unterminated template literal",
+];
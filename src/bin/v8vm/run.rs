@@ -0,0 +1,126 @@
+use std::fs::read_to_string;
+use std::process::exit;
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use v8vm::{Machine, ex::Fetch};
+use super::client::HttpClient;
+use super::print;
+
+struct Options {
+    file:    String,
+    export:  String,
+    args:    Vec<Value>,
+    srcmap:  Option<String>,
+    fetch:   bool,
+    queue:   Option<usize>,
+    limit:   Option<usize>,
+    heap:    Option<usize>,
+    timeout: Option<Duration>,
+}
+
+pub fn run(args: &[String]) -> Result<()> {
+    let options = Options::parse(args)?;
+    let module  = read_to_string(&options.file).with_context(|| options.file.clone())?;
+
+    let mut machine = Machine::new(module);
+    machine.name(options.file.clone());
+
+    if let Some(srcmap) = &options.srcmap {
+        machine.source_map(read_to_string(srcmap).with_context(|| srcmap.clone())?);
+    }
+
+    if options.fetch {
+        machine.extend(Fetch::new(HttpClient::new()));
+    }
+
+    if let Some(capacity) = options.queue {
+        machine.queue(capacity);
+    }
+
+    if let Some(limit) = options.limit {
+        machine.in_flight(limit);
+    }
+
+    if let Some(heap) = options.heap {
+        machine.heap_limit(heap * 1024 * 1024);
+    }
+
+    let (handle, guard) = machine.exec();
+
+    let result = handle.find(&options.export).and_then(|function| {
+        let mut rx = function.call(options.args)?;
+        match options.timeout {
+            Some(timeout) => rx.recv_timeout(timeout)?.ok_or_else(|| {
+                anyhow!("{} timed out after {:?}", options.export, timeout)
+            }),
+            None          => rx.recv(),
+        }
+    });
+
+    let failed = result.is_err();
+    print(result);
+    drop(guard);
+
+    if failed {
+        exit(1);
+    }
+
+    Ok(())
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self> {
+        let mut file    = None;
+        let mut export  = "default".to_owned();
+        let mut params  = Vec::new();
+        let mut srcmap  = None;
+        let mut fetch   = false;
+        let mut queue   = None;
+        let mut limit   = None;
+        let mut heap    = None;
+        let mut timeout = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("{arg} requires a value"));
+            match arg.as_str() {
+                "--export"     => export  = value()?.clone(),
+                "--args"       => params  = arguments(value()?)?,
+                "--source-map" => srcmap  = Some(value()?.clone()),
+                "--fetch"      => fetch   = true,
+                "--queue"      => queue   = Some(value()?.parse()?),
+                "--in-flight"  => limit   = Some(value()?.parse()?),
+                "--heap-limit" => heap    = Some(value()?.parse()?),
+                "--timeout"    => timeout = Some(Duration::from_millis(value()?.parse()?)),
+                flag if flag.starts_with("--") => return Err(anyhow!("unknown flag {flag}")),
+                path if file.is_none()         => file = Some(path.to_owned()),
+                path                           => return Err(anyhow!("unexpected argument {path}")),
+            }
+        }
+
+        Ok(Self {
+            file:    file.ok_or_else(|| anyhow!(USAGE))?,
+            export:  export,
+            args:    params,
+            srcmap:  srcmap,
+            fetch:   fetch,
+            queue:   queue,
+            limit:   limit,
+            heap:    heap,
+            timeout: timeout,
+        })
+    }
+}
+
+// a JSON array is spread as positional arguments, anything else is passed as one
+fn arguments(json: &str) -> Result<Vec<Value>> {
+    match serde_json::from_str(json).context("invalid --args")? {
+        Value::Array(args) => Ok(args),
+        value              => Ok(vec![value]),
+    }
+}
+
+pub const USAGE: &str = "usage: v8vm run <file.js> [--export name] [--args json] \
+                     [--fetch] [--source-map file] [--queue n] [--in-flight n] [--heap-limit mib] \
+                     [--timeout ms]";
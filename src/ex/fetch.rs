@@ -1,15 +1,16 @@
 use anyhow::Result;
-use http::StatusCode;
+use http::{HeaderMap, HeaderValue, StatusCode};
 use v8::{self, Global, HandleScope, Local, ObjectTemplate, Value};
-use crate::vm::{Adjunct, Promises, Resolved, Resolver};
+use crate::vm::{Adjunct, Promises, Resolved, Resolver, Trace};
 
 pub struct Fetch<C> {
     client: C,
 }
 
 pub struct Request {
-    pub method: String,
-    pub url:    String,
+    pub method:  String,
+    pub url:     String,
+    pub headers: HeaderMap,
 }
 
 pub struct Response {
@@ -197,9 +198,16 @@ fn fetch<C: Client>(
         Some(Err(_)) | None => "GET".to_owned(),
     };
 
+    let mut headers = HeaderMap::new();
+    if let Some(trace) = Trace::current(scope) {
+        let value = HeaderValue::from_str(&trace.to_string()).unwrap();
+        headers.insert("traceparent", value);
+    }
+
     let request = Request {
-        method: method,
-        url:    url,
+        method:  method,
+        url:     url,
+        headers: headers,
     };
 
     let data  = args.data().unwrap();
@@ -1,59 +1,267 @@
-#[cfg(not(feature = "tokio"))]
-use crossbeam_channel::{unbounded as channel, Sender, Receiver};
-#[cfg(feature = "tokio")]
-use std::{future::Future, pin::Pin, task::{Context, Poll}};
-use anyhow::{Error, Result};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, spawn, Thread};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Error, Result};
+use crossbeam_channel::unbounded;
+use futures_channel::oneshot::{channel, Sender, Receiver};
 use serde_json::Value;
-#[cfg(feature = "tokio")]
-use tokio::sync::oneshot::{channel, Sender, Receiver};
 
-pub struct Tx(Sender<Result<Value>>);
-pub struct Rx(Receiver<Result<Value>>);
+pub struct Tx {
+    sender: Sender<Result<Value>>,
+    sent:   Option<Box<dyn FnOnce() + Send>>,
+}
+
+pub struct Rx {
+    receiver: Option<Receiver<Result<Value>>>,
+    cancel:   Option<Box<dyn FnOnce() + Send>>,
+}
+
+pub struct Reply<T>(Sender<T>);
+
+pub struct Response<T>(Receiver<T>);
+
+pub struct Timeout<F> {
+    future:   F,
+    deadline: Instant,
+    waker:    Option<Arc<Mutex<Waker>>>,
+}
+
+struct Timer {
+    deadline: Instant,
+    waker:    Weak<Mutex<Waker>>,
+}
+
+struct Unpark(Thread);
 
 pub fn oneshot() -> (Tx, Rx) {
     let (tx, rx) = channel();
-    (Tx(tx), Rx(rx))
+    (Tx { sender: tx, sent: None }, Rx { receiver: Some(rx), cancel: None })
+}
+
+pub fn reply<T>() -> (Reply<T>, Response<T>) {
+    let (tx, rx) = channel();
+    (Reply(tx), Response(rx))
+}
+
+pub fn timeout<F: Future + Unpin>(future: F, timeout: Duration) -> Timeout<F> {
+    Timeout {
+        future:   future,
+        deadline: Instant::now() + timeout,
+        waker:    None,
+    }
 }
 
 impl Tx {
-    pub fn send(self, result: Result<Value, Value>) {
-        let result = result.map_err(|value| {
-            match value {
-                Value::String(s) => Error::msg(s),
-                value            => Error::msg(value),
-            }
-        });
+    pub(super) fn on_send<F: FnOnce() + Send + 'static>(&mut self, sent: F) {
+        self.sent = Some(Box::new(sent));
+    }
 
-        match self.0.send(result) {
+    pub fn send(self, result: Result<Value>) {
+        if let Some(sent) = self.sent {
+            sent();
+        }
+
+        match self.sender.send(result) {
             Ok(()) => (),
             Err(_) => (),
         }
     }
 }
 
-#[cfg(not(feature = "tokio"))]
-impl Rx {
-    pub fn recv(self) -> Result<Value> {
-        self.0.recv()?
+impl<T> Reply<T> {
+    pub fn send(self, value: T) -> Result<()> {
+        match self.0.send(value) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(anyhow!("receiver dropped")),
+        }
     }
 }
 
-#[cfg(feature = "tokio")]
 impl Rx {
-    pub fn recv(self) -> Result<Value> {
-        self.0.blocking_recv()?
+    pub(super) fn on_cancel<F: FnOnce() + Send + 'static>(&mut self, cancel: F) {
+        self.cancel = Some(Box::new(cancel));
+    }
+
+    pub fn cancel(self) {
+        drop(self);
+    }
+
+    pub fn recv(mut self) -> Result<Value> {
+        block(&mut self, None).unwrap_or_else(|| unreachable!())
+    }
+
+    pub fn try_recv(&mut self) -> Result<Option<Value>> {
+        let receiver = match self.receiver.as_mut() {
+            Some(receiver) => receiver,
+            None           => return Err(anyhow!("result already received")),
+        };
+
+        let result = match receiver.try_recv() {
+            Ok(Some(r)) => r,
+            Ok(None)    => return Ok(None),
+            Err(_)      => Err(terminated()),
+        };
+
+        self.receiver = None;
+        self.cancel   = None;
+
+        result.map(Some)
+    }
+
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Value>> {
+        match block(self, Some(Instant::now() + timeout)) {
+            Some(result) => result.map(Some),
+            None         => Ok(None),
+        }
+    }
+}
+
+impl<T> Response<T> {
+    pub fn recv(mut self) -> Result<T> {
+        block(&mut self, None).unwrap_or_else(|| unreachable!())
+    }
+
+    pub fn recv_timeout(mut self, timeout: Duration) -> Result<Option<T>> {
+        block(&mut self, Some(Instant::now() + timeout)).transpose()
     }
 }
 
-#[cfg(feature = "tokio")]
 impl Future for Rx {
     type Output = Result<Value>;
 
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let receiver = match self.receiver.as_mut() {
+            Some(receiver) => receiver,
+            None           => return Poll::Ready(Err(anyhow!("result already received"))),
+        };
+
+        let result = match Pin::new(receiver).poll(cx) {
+            Poll::Ready(Ok(r))  => r,
+            Poll::Ready(Err(_)) => Err(terminated()),
+            Poll::Pending       => return Poll::Pending,
+        };
+
+        self.receiver = None;
+        self.cancel   = None;
+
+        Poll::Ready(result)
+    }
+}
+
+impl<T> Future for Response<T> {
+    type Output = Result<T>;
+
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match Pin::new(&mut self.0).poll(cx) {
-            Poll::Ready(Ok(r))  => Poll::Ready(r),
-            Poll::Ready(Err(e)) => Poll::Ready(Err(e.into())),
-            Poll::Pending       => Poll::Pending,
+            Poll::Ready(Ok(value)) => Poll::Ready(Ok(value)),
+            Poll::Ready(Err(_))    => Poll::Ready(Err(terminated())),
+            Poll::Pending          => Poll::Pending,
+        }
+    }
+}
+
+impl<F: Future + Unpin> Future for Timeout<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = Pin::new(&mut self.future).poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+
+        let deadline = self.deadline;
+        if Instant::now() >= deadline {
+            return Poll::Ready(None);
+        }
+
+        match &self.waker {
+            Some(waker) => *waker.lock().unwrap() = cx.waker().clone(),
+            None        => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                schedule(deadline, &waker);
+                self.waker = Some(waker);
+            },
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Rx {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel();
+        }
+    }
+}
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// a single thread wakes every pending timeout rather than one sleeping thread each
+fn schedule(deadline: Instant, waker: &Arc<Mutex<Waker>>) {
+    static TIMERS: OnceLock<crossbeam_channel::Sender<Timer>> = OnceLock::new();
+
+    let timers = TIMERS.get_or_init(|| {
+        let (sender, receiver) = unbounded();
+        spawn(move || timers(receiver));
+        sender
+    });
+
+    let _ = timers.send(Timer { deadline, waker: Arc::downgrade(waker) });
+}
+
+fn timers(receiver: crossbeam_channel::Receiver<Timer>) {
+    let mut timers = Vec::<Timer>::new();
+
+    loop {
+        let timer = match timers.iter().map(|timer| timer.deadline).min() {
+            Some(deadline) => receiver.recv_deadline(deadline).ok(),
+            None           => match receiver.recv() {
+                Ok(timer) => Some(timer),
+                Err(_)    => return,
+            },
+        };
+        timers.extend(timer);
+
+        // wake what expired and forget timeouts that were dropped
+        let now = Instant::now();
+        timers.retain(|timer| match timer.waker.upgrade() {
+            Some(waker) if timer.deadline <= now => {
+                waker.lock().unwrap().wake_by_ref();
+                false
+            },
+            Some(_) => true,
+            None    => false,
+        });
+    }
+}
+
+// the sending half was dropped with the machine before it replied
+fn terminated() -> Error {
+    anyhow!("machine terminated")
+}
+
+pub fn block<F: Future + Unpin>(future: &mut F, deadline: Option<Instant>) -> Option<F::Output> {
+    let waker  = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = Pin::new(&mut *future).poll(&mut cx) {
+            return Some(output);
+        }
+
+        match deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) => thread::park_timeout(timeout),
+                None          => return None,
+            },
+            None => thread::park(),
         }
     }
 }
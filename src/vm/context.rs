@@ -1,115 +1,569 @@
-use std::fmt::Write;
+use std::collections::HashMap;
+use std::fs::{remove_file, rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Instant;
 use anyhow::{anyhow, Error, Result};
-use crossbeam_channel::Sender;
 use serde_json::Value;
-use v8::{self, ContextScope, Function, HandleScope, Local, Weak};
-use v8::script_compiler::{compile_module, Source};
-use super::channel::Tx;
+use tracing::{info_span, Span};
+use tracing::span::EnteredSpan;
+use v8::{self, ContextScope, Function, Global, HandleScope, Local, Weak};
+use v8::script_compiler::{compile_module, compile_module2, compile_unbound_script};
+use v8::script_compiler::{CachedData, CompileOptions, NoCacheReason, Source};
+use super::adjunct::Adjunct;
+use super::channel::{Reply, Tx};
+use super::exports::{self, List, Read};
+use super::failure::{failure, Failure};
+use super::metrics::{Metrics, Outcome};
+#[cfg(feature = "metrics")]
+use super::metrics::Heap;
 use super::promise::{Promise, Promises};
+use super::signal::{self, Signal};
+use super::state::State;
+use super::supervise::Restarted;
+use super::trace::{self, Trace};
 
 pub struct Context<'i, 's> {
     pub context: Local<'s, v8::Context>,
     pub scope:   ContextScope<'i, HandleScope<'s>>,
-    pub exports: Local<'s, v8::Object>,
+    pub exports: Global<v8::Object>,
+    module:      Global<v8::Module>,
+    realms:      HashMap<String, Instance>,
+    pristine:    Option<Pristine>,
+    generation:  u64,
+    fixed:       Vec<String>,
 }
 
 pub struct Call {
+    pub id:     u64,
     pub export: Export,
     pub args:   Vec<Value>,
     pub sender: Tx,
+    pub start:  Instant,
+    pub trace:  Trace,
+    pub span:   Span,
 }
 
 pub struct Find {
+    pub realm:  Option<Arc<String>>,
     pub export: Arc<String>,
-    pub sender: Sender<Result<Export>>,
+    pub sender: Reply<Result<Export>>,
 }
 
+pub struct Get {
+    pub name:   String,
+    pub sender: Reply<Result<Value>>,
+}
+
+pub struct Set {
+    pub name:   String,
+    pub value:  Value,
+    pub sender: Reply<Result<()>>,
+}
+
+pub struct Eval {
+    pub id:     u64,
+    pub source: String,
+    pub sender: Tx,
+}
+
+pub struct Reload {
+    pub module: String,
+    pub sender: Reply<Result<()>>,
+}
+
+pub struct Stats {
+    pub sender: Reply<v8::HeapStatistics>,
+}
+
+pub struct Snapshot {
+    pub path:   PathBuf,
+    pub sender: Reply<Result<()>>,
+}
+
+struct Calls {
+    pending: HashMap<u64, Pending>,
+    metrics: Metrics,
+}
+
+struct Pending {
+    sender:   Tx,
+    abort:    Global<v8::Object>,
+    signal:   Global<v8::Value>,
+    export:   Arc<String>,
+    start:    Instant,
+    returned: Instant,
+    span:     Span,
+    trace:    Trace,
+}
+
+// id of the call whose code is running
+struct Current(u64);
+
+// private keys created once per isolate
+struct Keys {
+    call:  Global<v8::Private>,
+    abort: Global<v8::Private>,
+}
+
+// slots a promise job displaced while it runs on behalf of its call
+struct Job {
+    _span:   EnteredSpan,
+    current: Option<Current>,
+    trace:   Option<Trace>,
+    signal:  Option<Signal>,
+}
+
+struct Instance {
+    context: Global<v8::Context>,
+    exports: Global<v8::Object>,
+}
+
+struct Pristine {
+    template: Global<v8::ObjectTemplate>,
+    name:     String,
+    module:   String,
+    globals:  Vec<(String, Value)>,
+    values:   Vec<(String, Value)>,
+    cache:    Cache,
+}
+
+// compiled code each fresh context reuses instead of parsing source again
+struct Cache {
+    prelude: Global<v8::UnboundScript>,
+    module:  Option<Vec<u8>>,
+}
+
+// count of modules compiled from source alone
+struct Compiles(u64);
+
 #[derive(Clone)]
 pub struct Export {
-    weak: Arc<Weak<Function>>,
+    name:       Arc<String>,
+    realm:      Option<Arc<String>>,
+    weak:       Arc<Weak<Function>>,
+    generation: u64,
 }
 
 unsafe impl Send for Export {}
 unsafe impl Sync for Export {}
 
 impl<'i, 's> Context<'i, 's> {
-    pub fn new(mut scope: ContextScope<'i, HandleScope<'s>>, module: &str) -> Result<Self> {
+    pub fn new(
+        mut scope: ContextScope<'i, HandleScope<'s>>,
+        name:      &str,
+        module:    &str,
+        globals:   &[(String, Value)],
+        metrics:   Metrics,
+    ) -> Result<Self> {
         let context = scope.get_current_context();
 
-        let exports = {
-            let scope = &mut v8::TryCatch::new(&mut scope);
+        let keys = Keys {
+            call:  private(&mut scope, "v8vm#call"),
+            abort: private(&mut scope, "v8vm#abort"),
+        };
+        scope.set_slot(Rc::new(keys));
 
-            let module = match compile(scope, module) {
-                Some(module) => module,
-                None         => return Err(failure(scope)),
-            };
+        let module  = evaluate(&mut scope, name, module, globals, &[], None)?;
+        let exports = namespace(&mut scope, module);
+        let exports = Global::new(&mut scope, exports);
+        let module  = Global::new(&mut scope, module);
 
-            let object = module.get_module_namespace();
-            object.to_object(scope).unwrap()
-        };
+        scope.set_slot(Calls {
+            pending: HashMap::new(),
+            metrics: metrics,
+        });
+        scope.set_promise_hook(hook);
 
         Ok(Self {
-            context: context,
-            scope:   scope,
-            exports: exports,
+            context:    context,
+            scope:      scope,
+            exports:    exports,
+            module:     module,
+            realms:     HashMap::new(),
+            pristine:   None,
+            generation: 0,
+            fixed:      globals.iter().map(|(name, _)| name.clone()).collect(),
         })
     }
 
-    pub fn call(&mut self, Call { export, args, sender }: Call) -> Result<()> {
+    pub fn reload(&mut self, name: &str, module: &str) -> Result<()> {
+        let scope = &mut v8::HandleScope::new(&mut self.scope);
+        let scope = &mut v8::TryCatch::new(scope);
+
+        let compiled = match compile(scope, name, module, None) {
+            Some(module) => module,
+            None         => return Err(failure(scope)),
+        };
+
+        // a top-level throw rejects the evaluation promise, keep the old
+        // module rather than swap in a half-initialised one
+        if compiled.get_status() == v8::ModuleStatus::Errored {
+            let exception = compiled.get_exception();
+            scope.throw_exception(exception);
+            return Err(failure(scope));
+        }
+
+        let exports  = namespace(scope, compiled);
+        self.exports = Global::new(scope, exports);
+        self.module  = Global::new(scope, compiled);
+        self.generation += 1;
+
+        if let Some(pristine) = &mut self.pristine {
+            pristine.module       = module.to_owned();
+            pristine.cache.module = cache(scope, compiled);
+        }
+
+        Ok(())
+    }
+
+    pub fn pristine(
+        &mut self,
+        template: Global<v8::ObjectTemplate>,
+        name:     &str,
+        module:   &str,
+        globals:  &[(String, Value)],
+    ) -> Result<()> {
+        let scope = &mut v8::HandleScope::new(&mut self.scope);
+        let scope = &mut v8::TryCatch::new(scope);
+
+        let prelude = match prelude(scope) {
+            Some(prelude) => Global::new(scope, prelude),
+            None          => return Err(failure(scope)),
+        };
+
+        let compiled = Local::new(scope, &self.module);
+        self.pristine = Some(Pristine {
+            template: template,
+            name:     name.to_owned(),
+            module:   module.to_owned(),
+            globals:  globals.to_vec(),
+            values:   Vec::new(),
+            cache:    Cache {
+                prelude: prelude,
+                module:  cache(scope, compiled),
+            },
+        });
+
+        Ok(())
+    }
+
+    pub fn realm(&mut self, name: &str, module: &str, extra: &[Box<dyn Adjunct>]) -> Result<()> {
+        let scope  = &mut v8::HandleScope::new(&mut self.scope);
+        let global = v8::ObjectTemplate::new(scope);
+        global.set_internal_field_count(1);
+
+        for adjunct in extra {
+            adjunct.install(scope, &global);
+        }
+
+        let (context, exports) = instantiate(scope, self.context, global, name, module, None)?;
+
+        self.realms.insert(name.to_owned(), Instance {
+            context: Global::new(scope, context),
+            exports: Global::new(scope, exports),
+        });
+
+        Ok(())
+    }
+
+    pub fn call(&mut self, call: Call, state: &State) -> Result<()> {
+        let Call { id, export, args, sender, start, trace, span } = call;
+
+        let span  = info_span!(parent: &span, "call", export = %export.name, trace = %trace);
+        let _span = span.enter();
+
         let scope = &mut v8::HandleScope::new(&mut self.scope);
+        let (context, exports) = match (&export.realm, &self.pristine) {
+            (Some(realm), _) => match enter(scope, &self.realms, realm) {
+                Some(realm) => realm,
+                None        => {
+                    sender.send(Err(anyhow!("realm gone")));
+                    return Ok(());
+                },
+            },
+            (None, Some(pristine)) => {
+                let Pristine { name, module, .. } = pristine;
+                let template = Local::new(scope, &pristine.template);
+                match instantiate(scope, self.context, template, name, module, Some(pristine)) {
+                    Ok(realm) => realm,
+                    Err(e)    => {
+                        sender.send(Err(e));
+                        return Ok(());
+                    },
+                }
+            },
+            (None, None) => (self.context, Local::new(scope, &self.exports)),
+        };
+
+        // a fresh context must never reach back into the pristine one, and
+        // handles from before a reload must resolve against the new module
+        let stale = self.pristine.is_some() || export.generation != self.generation;
+        let stale = export.realm.is_none() && stale;
+
+        let scope = &mut v8::ContextScope::new(scope, context);
         let scope = &mut v8::TryCatch::new(scope);
 
-        let func = match export.weak.to_local(scope) {
+        let func = match stale {
+            true  => None,
+            false => export.weak.to_local(scope),
+        };
+
+        let func = match func {
+            Some(func) => Some(func),
+            None       => function(scope, exports, &export.name),
+        };
+
+        let func = match func {
             Some(func) => func,
-            None       => return Err(anyhow!("export gone")),
+            None       => {
+                sender.send(Err(anyhow!("export gone")));
+                return Ok(());
+            },
         };
 
-        let this = self.context.global(scope).into();
+        let this = context.global(scope).into();
         let args = args.into_iter().map(|arg| {
             Ok(serde_v8::to_v8(scope, arg)?)
         }).collect::<Result<Vec<_>>>()?;
 
-        let result = match func.call(scope, this, &args) {
+        let abort = match controller(scope) {
+            Ok(abort) => abort,
+            Err(e)    => {
+                sender.send(Err(e));
+                return Ok(());
+            },
+        };
+
+        let signal = abort_signal(scope, abort);
+        let signal = Global::new(scope, signal);
+
+        if !state.start(id) {
+            sender.send(Err(anyhow!("call cancelled")));
+            return Ok(());
+        }
+
+        scope.set_slot(trace);
+        scope.set_slot(Current(id));
+        scope.set_slot(Signal(signal.clone()));
+        let result = func.call(scope, this, &args);
+        let halted = scope.has_terminated();
+        scope.remove_slot::<Trace>();
+        scope.remove_slot::<Current>();
+        scope.remove_slot::<Signal>();
+
+        state.finish();
+
+        let result = match result {
             Some(result) => result,
             None         => v8::undefined(scope).into(),
         };
 
+        if halted {
+            state.metrics.call(&export.name, Outcome::Cancelled, start.elapsed());
+            sender.send(Err(halt(state)));
+            return Ok(());
+        }
+
         if !result.is_promise() {
             let value = match scope.exception() {
                 None    => Ok(serde_v8::from_v8(scope, result)?),
-                Some(e) => Err(cause(scope, e)),
+                Some(e) => Err(Failure::exception(scope, e).into()),
             };
+            state.metrics.call(&export.name, Outcome::from(&value), start.elapsed());
             sender.send(value);
             return Ok(());
         }
 
-        let tx = Box::leak(Box::new(sender)) as *mut Tx;
-        let tx = v8::External::new(scope, tx as _).into();
+        let abort = Global::new(scope, abort);
+        wait(scope, id, result, Pending {
+            sender:   sender,
+            abort:    abort,
+            signal:   signal,
+            export:   export.name.clone(),
+            start:    start,
+            returned: Instant::now(),
+            span:     span.clone(),
+            trace:    trace,
+        })
+    }
+
+    pub fn find(&mut self, Find { realm, export, sender }: Find) -> Result<()> {
+        let scope = &mut v8::HandleScope::new(&mut self.scope);
 
-        let resolved = v8::Function::builder(resolved).data(tx).build(scope).unwrap();
-        let rejected = v8::Function::builder(rejected).data(tx).build(scope).unwrap();
+        let exports = match &realm {
+            None       => Local::new(scope, &self.exports),
+            Some(name) => match enter(scope, &self.realms, name) {
+                Some((_, exports)) => exports,
+                None               => {
+                    let error = anyhow!("{name} is not a realm");
+                    return sender.send(Err(error)).or(Ok(()));
+                },
+            },
+        };
 
-        let promise = v8::Local::<v8::Promise>::try_from(result)?;
-        promise.then2(scope, resolved, rejected).unwrap();
+        let result = match function(scope, exports, &export) {
+            Some(f) => {
+                let weak = Weak::new(scope, f);
+                Ok(Export::new(export.clone(), realm, weak, self.generation))
+            },
+            None    => Err(anyhow!("{export} is not a function")),
+        };
 
-        Ok(())
+        sender.send(result).or(Ok(()))
     }
 
-    pub fn find(&mut self, Find { export, sender }: Find) -> Result<()> {
+    pub fn list(&mut self, list: List) -> Result<()> {
         let scope = &mut v8::HandleScope::new(&mut self.scope);
+        let exports = Local::new(scope, &self.exports);
+        exports::list(scope, exports, list)
+    }
 
-        let name = v8::String::new(scope, &export).unwrap();
-        let func = match self.exports.get(scope, name.into()) {
-            Some(func) => func,
-            None       => v8::undefined(scope).into(),
+    pub fn read(&mut self, read: Read) -> Result<()> {
+        let scope = &mut v8::HandleScope::new(&mut self.scope);
+        let exports = Local::new(scope, &self.exports);
+        exports::read(scope, exports, read)
+    }
+
+    pub fn get(&mut self, Get { name, sender }: Get) -> Result<()> {
+        let scope  = &mut v8::HandleScope::new(&mut self.scope);
+        let scope  = &mut v8::TryCatch::new(scope);
+        let global = self.context.global(scope);
+
+        let key    = v8::String::new(scope, &name).unwrap();
+        let result = match global.has(scope, key.into()) {
+            Some(true) => match global.get(scope, key.into()) {
+                Some(value) => serde_v8::from_v8(scope, value).map_err(Error::from),
+                None        => Err(failure(scope)),
+            },
+            Some(false) => Err(anyhow!("{name} is not defined")),
+            None        => Err(failure(scope)),
+        };
+
+        sender.send(result).or(Ok(()))
+    }
+
+    pub fn set(&mut self, Set { name, value, sender }: Set) -> Result<()> {
+        let scope  = &mut v8::HandleScope::new(&mut self.scope);
+        let scope  = &mut v8::TryCatch::new(scope);
+        let global = self.context.global(scope);
+
+        if self.fixed.contains(&name) {
+            return sender.send(Err(anyhow!("{name} is read-only"))).or(Ok(()));
+        }
+
+        let result = assign(scope, global, &name, value.clone()).and_then(|assigned| {
+            match assigned {
+                true  => Ok(()),
+                false => Err(failure(scope)),
+            }
+        });
+
+        // each fresh context is built from the pristine values, not this one
+        if let (Ok(()), Some(pristine)) = (&result, &mut self.pristine) {
+            match pristine.values.iter_mut().find(|(key, _)| *key == name) {
+                Some((_, slot)) => *slot = value,
+                None            => pristine.values.push((name, value)),
+            }
+        }
+
+        sender.send(result).or(Ok(()))
+    }
+
+    pub fn eval(&mut self, Eval { id, source, sender }: Eval, state: &State) -> Result<()> {
+        let scope = &mut v8::HandleScope::new(&mut self.scope);
+        let scope = &mut v8::TryCatch::new(scope);
+        let start = Instant::now();
+        let trace = Trace::new();
+
+        let abort = match controller(scope) {
+            Ok(abort) => abort,
+            Err(e)    => {
+                sender.send(Err(e));
+                return Ok(());
+            },
         };
 
-        let result = match v8::Local::<v8::Function>::try_from(func) {
-            Ok(f)  => Ok(Export::new(Weak::new(scope, f))),
-            Err(_) => Err(anyhow!("{export} is not a function")),
+        let signal = abort_signal(scope, abort);
+        let signal = Global::new(scope, signal);
+
+        if !state.start(id) {
+            sender.send(Err(anyhow!("call cancelled")));
+            return Ok(());
+        }
+
+        scope.set_slot(trace);
+        scope.set_slot(Current(id));
+        scope.set_slot(Signal(signal.clone()));
+        let value  = script(scope, "<eval>", &source);
+        let halted = scope.has_terminated();
+        scope.remove_slot::<Trace>();
+        scope.remove_slot::<Current>();
+        scope.remove_slot::<Signal>();
+
+        state.finish();
+
+        if halted {
+            sender.send(Err(halt(state)));
+            return Ok(());
+        }
+
+        let value = match value {
+            Some(value) => value,
+            None        => {
+                sender.send(Err(failure(scope)));
+                return Ok(());
+            },
         };
 
+        if !value.is_promise() {
+            sender.send(serde_v8::from_v8(scope, value).map_err(Error::from));
+            return Ok(());
+        }
+
+        let abort = Global::new(scope, abort);
+        wait(scope, id, value, Pending {
+            sender:   sender,
+            abort:    abort,
+            signal:   signal,
+            export:   Arc::new("<eval>".to_owned()),
+            start:    start,
+            returned: Instant::now(),
+            span:     Span::current(),
+            trace:    trace,
+        })
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn heap(&mut self) -> Heap {
+        let mut stats = v8::HeapStatistics::default();
+        self.scope.get_heap_statistics(&mut stats);
+        Heap {
+            used:  stats.used_heap_size(),
+            total: stats.total_heap_size(),
+            limit: stats.heap_size_limit(),
+        }
+    }
+
+    pub fn stats(&mut self, Stats { sender }: Stats) -> Result<()> {
+        let mut stats = v8::HeapStatistics::default();
+        self.scope.get_heap_statistics(&mut stats);
+        sender.send(stats).or(Ok(()))
+    }
+
+    pub fn snapshot(&mut self, Snapshot { path, sender }: Snapshot) -> Result<()> {
+        // write beside the target and rename, a failed write leaves nothing behind
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let result = dump(&mut self.scope, &temp).and_then(|()| Ok(rename(&temp, &path)?));
+        if result.is_err() {
+            let _ = remove_file(&temp);
+        }
+
         sender.send(result).or(Ok(()))
     }
 
@@ -123,6 +577,46 @@ impl<'i, 's> Context<'i, 's> {
         Ok(())
     }
 
+    pub fn cancel(&mut self, id: u64) -> Result<()> {
+        let scope   = &mut v8::HandleScope::new(&mut self.scope);
+        let pending = scope.get_slot_mut::<Calls>().and_then(|calls| {
+            let pending = calls.pending.remove(&id)?;
+            Some((pending, calls.metrics.clone()))
+        });
+
+        if let Some((Pending { sender, abort, export, start, .. }, metrics)) = pending {
+            metrics.call(&export, Outcome::Cancelled, start.elapsed());
+
+            let abort = Local::new(scope, abort);
+            let name  = v8::String::new(scope, "abort").unwrap();
+            let func  = abort.get(scope, name.into()).unwrap();
+            let func  = v8::Local::<v8::Function>::try_from(func)?;
+
+            let scope = &mut v8::TryCatch::new(scope);
+            func.call(scope, abort.into(), &[]);
+
+            sender.send(Err(anyhow!("call cancelled")));
+        }
+
+        Ok(())
+    }
+
+    pub fn restart(&mut self) {
+        if let Some(calls) = self.scope.get_slot_mut::<Calls>() {
+            for (_, Pending { sender, .. }) in calls.pending.drain() {
+                sender.send(Err(Restarted.into()));
+            }
+        }
+    }
+
+    pub fn in_flight(&mut self) -> usize {
+        self.scope.get_slot::<Calls>().map_or(0, |calls| calls.pending.len())
+    }
+
+    pub fn compiles(&mut self) -> u64 {
+        self.scope.get_slot::<Compiles>().map_or(0, |compiles| compiles.0)
+    }
+
     pub fn tick(&mut self) {
         let platform = &v8::V8::get_current_platform();
         let scope    = &mut self.scope;
@@ -131,9 +625,34 @@ impl<'i, 's> Context<'i, 's> {
     }
 }
 
+impl Calls {
+    fn settle(scope: &mut HandleScope, id: Local<v8::Value>, result: Result<Value>) {
+        let id    = id.integer_value(scope).unwrap_or_default() as u64;
+        let calls = match scope.get_slot_mut::<Calls>() {
+            Some(calls) => calls,
+            None        => return,
+        };
+
+        if let Some(Pending { sender, export, start, returned, span, .. }) = calls.pending.remove(&id) {
+            let _span = span.enter();
+            calls.metrics.call(&export, Outcome::from(&result), start.elapsed());
+            calls.metrics.settle(&export, returned.elapsed());
+            sender.send(result);
+        }
+    }
+}
+
+impl Drop for Calls {
+    fn drop(&mut self) {
+        for (_, Pending { sender, .. }) in self.pending.drain() {
+            sender.send(Err(anyhow!("machine stopped")));
+        }
+    }
+}
+
 impl Export {
-    fn new(weak: Weak<Function>) -> Self {
-        Self { weak: Arc::new(weak) }
+    fn new(name: Arc<String>, realm: Option<Arc<String>>, weak: Weak<Function>, generation: u64) -> Self {
+        Self { name, realm, weak: Arc::new(weak), generation }
     }
 }
 
@@ -144,13 +663,10 @@ fn resolved(
 ) {
     let scope = &mut v8::HandleScope::new(scope);
 
-    let data  = args.data().unwrap();
-    let data  = v8::Local::<v8::External>::try_from(data).unwrap();
-    let tx    = unsafe { Box::from_raw(data.value() as *mut Tx) };
-
-    let value = serde_v8::from_v8(scope, args.get(0)).unwrap();
+    let id    = args.data().unwrap();
+    let value = serde_v8::from_v8(scope, args.get(0)).map_err(Error::from);
 
-    tx.send(Ok(value));
+    Calls::settle(scope, id, value);
 }
 
 fn rejected(
@@ -160,44 +676,340 @@ fn rejected(
 ) {
     let scope = &mut v8::HandleScope::new(scope);
 
-    let data  = args.data().unwrap();
-    let data  = v8::Local::<v8::External>::try_from(data).unwrap();
-    let tx    = unsafe { Box::from_raw(data.value() as *mut Tx) };
-
-    let value = cause(scope, args.get(0));
+    let id    = args.data().unwrap();
+    let value = Failure::exception(scope, args.get(0));
 
-    tx.send(Err(value));
+    Calls::settle(scope, id, Err(value.into()));
 }
 
-fn cause<'a>(scope: &'a mut HandleScope, mut value: Local<'a, v8::Value>) -> Value {
-    if let Ok(object) = v8::Local::<v8::Object>::try_from(value) {
-        let context = scope.get_current_context();
-        let global  = context.global(scope);
+// promise jobs run after the call that queued them has returned, so tag
+// each promise with its call and restore that call's trace and span while
+// the promise's jobs run
+extern "C" fn hook(kind: v8::PromiseHookType, promise: Local<v8::Promise>, _parent: Local<v8::Value>) {
+    if kind == v8::PromiseHookType::Resolve {
+        return;
+    }
 
-        let name  = v8::String::new(scope, "Error").unwrap();
-        let error = global.get(scope, name.into()).unwrap();
+    let scope = &mut unsafe { v8::CallbackScope::new(promise) };
+    let scope = &mut v8::HandleScope::new(scope);
+
+    match kind {
+        v8::PromiseHookType::Init => {
+            let id = match scope.get_slot::<Current>() {
+                Some(current) => current.0,
+                None          => return,
+            };
 
-        if let Ok(error) = v8::Local::<v8::Object>::try_from(error) {
-            if let Some(true) = object.instance_of(scope, error) {
-                let name = v8::String::new(scope, "toString").unwrap();
-                let func = object.get(scope, name.into()).unwrap();
-                if let Ok(func) = v8::Local::<v8::Function>::try_from(func) {
-                    value = func.call(scope, object.into(), &[]).unwrap();
+            let key = match key(scope) {
+                Some(key) => key,
+                None      => return,
+            };
+
+            let id = v8::Number::new(scope, id as f64);
+            promise.set_private(scope, key, id.into());
+        },
+        v8::PromiseHookType::Before => {
+            let key = match key(scope) {
+                Some(key) => key,
+                None      => return,
+            };
+
+            let id = match promise.get_private(scope, key) {
+                Some(id) if id.is_number() => id.integer_value(scope).unwrap_or_default() as u64,
+                _                          => return,
+            };
+
+            let pending = scope.get_slot::<Calls>().and_then(|calls| {
+                calls.pending.get(&id).map(|p| (p.span.clone(), p.trace, p.signal.clone()))
+            });
+
+            if let Some((span, trace, signal)) = pending {
+                let current = scope.remove_slot::<Current>();
+                let prior   = scope.remove_slot::<Trace>();
+                let outer   = scope.remove_slot::<Signal>();
+                scope.set_slot(trace);
+                scope.set_slot(Current(id));
+                scope.set_slot(Signal(signal));
+                scope.set_slot(Job {
+                    _span:   span.entered(),
+                    current: current,
+                    trace:   prior,
+                    signal:  outer,
+                });
+            }
+        },
+        v8::PromiseHookType::After => {
+            if let Some(Job { current, trace, signal, .. }) = scope.remove_slot::<Job>() {
+                scope.remove_slot::<Current>();
+                scope.remove_slot::<Trace>();
+                scope.remove_slot::<Signal>();
+                if let Some(current) = current {
+                    scope.set_slot(current);
+                }
+                if let Some(trace) = trace {
+                    scope.set_slot(trace);
+                }
+                if let Some(signal) = signal {
+                    scope.set_slot(signal);
                 }
             }
+        },
+        v8::PromiseHookType::Resolve => (),
+    }
+}
+
+fn key<'s>(scope: &mut HandleScope<'s>) -> Option<Local<'s, v8::Private>> {
+    let keys = scope.get_slot::<Rc<Keys>>()?.clone();
+    Some(Local::new(scope, &keys.call))
+}
+
+fn private(scope: &mut HandleScope, name: &str) -> Global<v8::Private> {
+    let name = v8::String::new(scope, name).unwrap();
+    let key  = v8::Private::for_api(scope, Some(name));
+    Global::new(scope, key)
+}
+
+fn wait(
+    scope:   &mut HandleScope,
+    id:      u64,
+    promise: Local<v8::Value>,
+    pending: Pending,
+) -> Result<()> {
+    let calls = scope.get_slot_mut::<Calls>().unwrap();
+    calls.pending.insert(id, pending);
+
+    let id = v8::Number::new(scope, id as f64).into();
+
+    let resolved = v8::Function::builder(resolved).data(id).build(scope).unwrap();
+    let rejected = v8::Function::builder(rejected).data(id).build(scope).unwrap();
+
+    let promise = v8::Local::<v8::Promise>::try_from(promise)?;
+    promise.then2(scope, resolved, rejected).unwrap();
+
+    Ok(())
+}
+
+fn enter<'s>(
+    scope:  &mut HandleScope<'s>,
+    realms: &HashMap<String, Instance>,
+    name:   &str,
+) -> Option<(Local<'s, v8::Context>, Local<'s, v8::Object>)> {
+    let realm = realms.get(name)?;
+    Some((Local::new(scope, &realm.context), Local::new(scope, &realm.exports)))
+}
+
+fn instantiate<'s>(
+    scope:    &mut HandleScope<'s>,
+    main:     Local<v8::Context>,
+    template: Local<v8::ObjectTemplate>,
+    name:     &str,
+    module:   &str,
+    pristine: Option<&Pristine>,
+) -> Result<(Local<'s, v8::Context>, Local<'s, v8::Object>)> {
+    // every context shares the machine's promise table
+    let promises = main.global(scope).get_internal_field(scope, 0).unwrap();
+
+    let context = v8::Context::new_from_template(scope, template);
+    let scope   = &mut v8::ContextScope::new(scope, context);
+    context.global(scope).set_internal_field(0, promises);
+
+    let module  = match pristine {
+        Some(pristine) => evaluate(scope, name, module, &pristine.globals, &pristine.values, Some(&pristine.cache))?,
+        None           => evaluate(scope, name, module, &[], &[], None)?,
+    };
+    let exports = namespace(scope, module);
+    Ok((context, exports))
+}
+
+fn evaluate<'s>(
+    scope:   &mut HandleScope<'s>,
+    name:    &str,
+    module:  &str,
+    globals: &[(String, Value)],
+    values:  &[(String, Value)],
+    cache:   Option<&Cache>,
+) -> Result<Local<'s, v8::Module>> {
+    let scope = &mut v8::TryCatch::new(scope);
+
+    let prelude = match cache {
+        Some(cache) => Local::new(scope, &cache.prelude).bind_to_current_context(scope).run(scope),
+        None        => script(scope, "<prelude>", PRELUDE),
+    };
+
+    if prelude.is_none() {
+        return Err(failure(scope));
+    }
+
+    trace::install(scope);
+    signal::install(scope);
+
+    // calls use the prelude's constructor even if a script replaces it
+    let global = scope.get_current_context().global(scope);
+    let keys   = scope.get_slot::<Rc<Keys>>().unwrap().clone();
+    let key    = Local::new(scope, &keys.abort);
+    let ctor   = v8::String::new(scope, "AbortController").unwrap();
+    if let Some(ctor) = global.get(scope, ctor.into()) {
+        global.set_private(scope, key, ctor);
+    }
+    for (name, value) in globals {
+        if !define(scope, global, name, value.clone())? {
+            return Err(failure(scope));
+        }
+    }
+    for (name, value) in values {
+        if !assign(scope, global, name, value.clone())? {
+            return Err(failure(scope));
+        }
+    }
+
+    let cache = cache.and_then(|cache| cache.module.as_deref());
+    match compile(scope, name, module, cache) {
+        Some(module) => Ok(module),
+        None         => Err(failure(scope)),
+    }
+}
+
+fn namespace<'s>(scope: &mut HandleScope<'s>, module: Local<v8::Module>) -> Local<'s, v8::Object> {
+    let object = module.get_module_namespace();
+    object.to_object(scope).unwrap()
+}
+
+fn cache(scope: &mut HandleScope, module: Local<v8::Module>) -> Option<Vec<u8>> {
+    let unbound = module.get_unbound_module_script(scope);
+    unbound.create_code_cache().map(|data| data.to_vec())
+}
+
+fn dump(isolate: &mut v8::Isolate, path: &Path) -> Result<()> {
+    let mut file  = File::create(path)?;
+    let mut error = None;
+
+    isolate.take_heap_snapshot(|chunk| {
+        match file.write_all(chunk) {
+            Ok(()) => true,
+            Err(e) => { error = Some(e); false },
         }
+    });
+
+    match error {
+        None    => Ok(file.flush()?),
+        Some(e) => Err(e.into()),
+    }
+}
+
+fn halt(state: &State) -> Error {
+    match state.exhausted() {
+        true  => anyhow!("heap limit reached"),
+        false => anyhow!("call cancelled"),
     }
-    serde_v8::from_v8(scope, value).unwrap()
+}
+
+fn define(
+    scope:  &mut HandleScope,
+    global: Local<v8::Object>,
+    name:   &str,
+    value:  Value,
+) -> Result<bool> {
+    let key   = v8::String::new(scope, name).ok_or_else(|| anyhow!("invalid name {name}"))?;
+    let value = serde_v8::to_v8(scope, value)?;
+    let attr  = v8::READ_ONLY + v8::DONT_DELETE;
+    Ok(global.define_own_property(scope, key.into(), value, attr).unwrap_or(false))
+}
+
+fn assign(
+    scope:  &mut HandleScope,
+    global: Local<v8::Object>,
+    name:   &str,
+    value:  Value,
+) -> Result<bool> {
+    let key   = v8::String::new(scope, name).ok_or_else(|| anyhow!("invalid name {name}"))?;
+    let value = serde_v8::to_v8(scope, value)?;
+    Ok(global.set(scope, key.into(), value).unwrap_or(false))
+}
+
+fn controller<'s>(scope: &mut HandleScope<'s>) -> Result<Local<'s, v8::Object>> {
+    let context = scope.get_current_context();
+    let global  = context.global(scope);
+
+    let keys = scope.get_slot::<Rc<Keys>>().unwrap().clone();
+    let key  = Local::new(scope, &keys.abort);
+    let ctor = global.get_private(scope, key).ok_or_else(|| anyhow!("AbortController unavailable"))?;
+    let ctor = v8::Local::<v8::Function>::try_from(ctor)?;
+
+    match ctor.new_instance(scope, &[]) {
+        Some(abort) => Ok(abort),
+        None        => Err(anyhow!("AbortController unavailable")),
+    }
+}
+
+fn abort_signal<'s>(scope: &mut HandleScope<'s>, abort: Local<v8::Object>) -> Local<'s, v8::Value> {
+    let name = v8::String::new(scope, "signal").unwrap();
+    match abort.get(scope, name.into()) {
+        Some(signal) => signal,
+        None         => v8::undefined(scope).into(),
+    }
+}
+
+fn function<'s>(
+    scope:   &mut HandleScope<'s>,
+    exports: Local<'s, v8::Object>,
+    name:    &str,
+) -> Option<Local<'s, Function>> {
+    let name = v8::String::new(scope, name)?;
+    let func = exports.get(scope, name.into())?;
+    Local::<Function>::try_from(func).ok()
+}
+
+fn script<'s>(
+    scope: &mut v8::TryCatch<v8::HandleScope<'s>>,
+    name:  &str,
+    code:  &str,
+) -> Option<v8::Local<'s, v8::Value>> {
+    let code   = v8::String::new(scope, code)?;
+    let origin = origin(scope, name, false)?;
+    let script = v8::Script::compile(scope, code, Some(&origin))?;
+    script.run(scope)
+}
+
+fn prelude<'s>(scope: &mut HandleScope<'s>) -> Option<Local<'s, v8::UnboundScript>> {
+    let code   = v8::String::new(scope, PRELUDE)?;
+    let origin = origin(scope, "<prelude>", false)?;
+    let source = Source::new(code, Some(&origin));
+    compile_unbound_script(scope, source, CompileOptions::NoCompileOptions, NoCacheReason::NoReason)
 }
 
 fn compile<'i, 's>(
     scope: &mut v8::TryCatch<'i, v8::HandleScope<'s>>,
-    code:  &str
+    name:  &str,
+    code:  &str,
+    cache: Option<&[u8]>,
 ) -> Option<v8::Local<'s, v8::Module>> {
     let code   = v8::String::new(scope, code)?;
-    let name   = v8::String::new(scope, "<script>")?;
+    let origin = origin(scope, name, true)?;
+
+    let module = match cache {
+        Some(cache) => {
+            let source = Source::new_with_cached_data(code, Some(&origin), CachedData::new(cache));
+            compile_module2(scope, source, CompileOptions::ConsumeCodeCache, NoCacheReason::NoReason)?
+        },
+        None        => {
+            match scope.get_slot_mut::<Compiles>() {
+                Some(compiles) => compiles.0 += 1,
+                None           => { scope.set_slot(Compiles(1)); },
+            }
+            compile_module(scope, Source::new(code, Some(&origin)))?
+        },
+    };
+
+    module.instantiate_module(scope, |_, _, _, _| None)?;
+    module.evaluate(scope)?;
+
+    Some(module)
+}
+
+fn origin<'s>(scope: &mut HandleScope<'s>, name: &str, module: bool) -> Option<v8::ScriptOrigin<'s>> {
+    let name   = v8::String::new(scope, name)?;
     let srcmap = v8::undefined(scope);
-    let origin = v8::ScriptOrigin::new(
+    Some(v8::ScriptOrigin::new(
         scope,
         name.into(),
         0,
@@ -207,41 +1019,8 @@ fn compile<'i, 's>(
         srcmap.into(),
         false,
         false,
-        true,
-    );
-
-    let source = Source::new(code, Some(&origin));
-
-    let module = compile_module(scope, source)?;
-    module.instantiate_module(scope, |_, _, _, _| None)?;
-    module.evaluate(scope)?;
-
-    Some(module)
-}
-
-pub fn failure(scope: &mut v8::TryCatch<v8::HandleScope>) -> Error {
-    anyhow!(message(scope).or_else(|| {
-        scope.exception().map(|s| s.to_rust_string_lossy(scope))
-    }).unwrap_or_else(|| {
-        "no exception or message".to_owned()
-    }))
+        module,
+    ))
 }
 
-fn message(scope: &mut v8::TryCatch<v8::HandleScope>) -> Option<String> {
-    let msg    = scope.message()?;
-    let text   = msg.get(scope).to_rust_string_lossy(scope);
-    let script = msg.get_script_resource_name(scope)?.to_rust_string_lossy(scope);
-    let source = msg.get_source_line(scope)?.to_rust_string_lossy(scope);
-    let line   = msg.get_line_number(scope)?;
-    let column = msg.get_start_column();
-    let length = msg.get_end_column().saturating_sub(column);
-
-    let mut e = String::new();
-    writeln!(&mut e, "{}", text).unwrap();
-    writeln!(&mut e, "{:>4}--> {}:{}:{}", "", script, line, column).unwrap();
-    writeln!(&mut e, "{:>4} |   ", "").unwrap();
-    writeln!(&mut e, "{:>4} | {}", line, source).unwrap();
-    writeln!(&mut e, "{:>4} | {:>3$}{:^>4$}", "", "", "", column, length).unwrap();
-
-    Some(e)
-}
+const PRELUDE: &str = include_str!("prelude.js");
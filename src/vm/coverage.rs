@@ -0,0 +1,202 @@
+use std::fmt::Write;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use super::channel::Reply;
+use super::inspect::Session;
+
+pub struct Coverage {
+    pub script:    String,
+    pub functions: Vec<FunctionCoverage>,
+    pub lines:     Vec<LineCoverage>,
+}
+
+pub struct FunctionCoverage {
+    pub name:   String,
+    pub ranges: Vec<CoverageRange>,
+}
+
+pub struct CoverageRange {
+    pub start: Position,
+    pub end:   Position,
+    pub count: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub offset: usize,
+    pub line:   usize,
+    pub column: usize,
+}
+
+pub struct LineCoverage {
+    pub line:  usize,
+    pub count: u64,
+}
+
+pub struct Collect {
+    pub sender: Reply<Result<Coverage>>,
+}
+
+struct Index {
+    starts: Vec<usize>,
+}
+
+pub fn start(session: &mut Session) -> Result<()> {
+    session.call("Profiler.enable", json!({}))?;
+    session.call("Profiler.startPreciseCoverage", json!({
+        "callCount": true,
+        "detailed":  true,
+    }))?;
+    Ok(())
+}
+
+pub fn collect(
+    session: Option<&mut Session>,
+    script:  &str,
+    source:  &str,
+    Collect { sender }: Collect,
+) -> Result<()> {
+    let result = match session {
+        Some(session) => take(session, script, source),
+        None          => Err(anyhow!("coverage not enabled")),
+    };
+    sender.send(result).or(Ok(()))
+}
+
+fn take(session: &mut Session, script: &str, source: &str) -> Result<Coverage> {
+    let result  = session.call("Profiler.takePreciseCoverage", json!({}))?;
+    let scripts = result["result"].as_array().cloned().unwrap_or_default();
+
+    let entry = scripts.iter().find(|entry| {
+        entry["url"].as_str() == Some(script)
+    }).ok_or_else(|| anyhow!("no coverage for {script}"))?;
+
+    let index = Index::new(source);
+
+    let functions = entry["functions"].as_array().into_iter().flatten().map(|function| {
+        let name   = function["functionName"].as_str().unwrap_or_default();
+        let ranges = function["ranges"].as_array().into_iter().flatten().map(|range| {
+            CoverageRange {
+                start: index.position(offset(&range["startOffset"])),
+                end:   index.position(offset(&range["endOffset"])),
+                count: range["count"].as_u64().unwrap_or_default(),
+            }
+        }).collect::<Vec<_>>();
+
+        let name = match (name, ranges.first()) {
+            ("", Some(range)) => format!("<anonymous>:{}", range.start.line),
+            ("", None)        => "<anonymous>".to_owned(),
+            (name, _)         => name.to_owned(),
+        };
+
+        FunctionCoverage { name, ranges }
+    }).collect::<Vec<_>>();
+
+    let lines = index.lines(source, &functions);
+
+    Ok(Coverage {
+        script:    script.to_owned(),
+        functions: functions,
+        lines:     lines,
+    })
+}
+
+impl Coverage {
+    pub fn lcov(&self) -> String {
+        let mut s = String::new();
+
+        writeln!(&mut s, "TN:").unwrap();
+        writeln!(&mut s, "SF:{}", self.script).unwrap();
+
+        for function in &self.functions {
+            if let Some(range) = function.ranges.first() {
+                writeln!(&mut s, "FN:{},{}", range.start.line, function.name).unwrap();
+            }
+        }
+
+        for function in &self.functions {
+            if let Some(range) = function.ranges.first() {
+                writeln!(&mut s, "FNDA:{},{}", range.count, function.name).unwrap();
+            }
+        }
+
+        let hit = self.functions.iter().filter(|f| f.count() > 0).count();
+        writeln!(&mut s, "FNF:{}", self.functions.len()).unwrap();
+        writeln!(&mut s, "FNH:{}", hit).unwrap();
+
+        for line in &self.lines {
+            writeln!(&mut s, "DA:{},{}", line.line, line.count).unwrap();
+        }
+
+        let hit = self.lines.iter().filter(|l| l.count > 0).count();
+        writeln!(&mut s, "LF:{}", self.lines.len()).unwrap();
+        writeln!(&mut s, "LH:{}", hit).unwrap();
+        writeln!(&mut s, "end_of_record").unwrap();
+
+        s
+    }
+}
+
+impl FunctionCoverage {
+    pub fn count(&self) -> u64 {
+        self.ranges.first().map(|range| range.count).unwrap_or_default()
+    }
+}
+
+impl Index {
+    fn new(source: &str) -> Self {
+        let mut starts = vec![0];
+        let mut offset = 0;
+
+        for c in source.chars() {
+            offset += c.len_utf16();
+            if c == '\n' {
+                starts.push(offset);
+            }
+        }
+
+        Self { starts }
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line)  => line,
+            Err(line) => line - 1,
+        };
+
+        Position {
+            offset: offset,
+            line:   line + 1,
+            column: offset - self.starts[line],
+        }
+    }
+
+    fn lines(&self, source: &str, functions: &[FunctionCoverage]) -> Vec<LineCoverage> {
+        let ranges = functions.iter().flat_map(|f| &f.ranges).collect::<Vec<_>>();
+
+        source.lines().zip(&self.starts).enumerate().filter_map(|(n, (line, start))| {
+            let indent = line.chars().take_while(|c| c.is_whitespace());
+            let indent = indent.map(char::len_utf16).sum::<usize>();
+
+            if line.trim().is_empty() {
+                return None;
+            }
+
+            let offset = start + indent;
+            let range  = ranges.iter().filter(|range| {
+                range.start.offset <= offset && offset < range.end.offset
+            }).min_by_key(|range| {
+                range.end.offset - range.start.offset
+            })?;
+
+            Some(LineCoverage {
+                line:  n + 1,
+                count: range.count,
+            })
+        }).collect()
+    }
+}
+
+fn offset(value: &Value) -> usize {
+    value.as_u64().unwrap_or_default() as usize
+}
@@ -0,0 +1,109 @@
+use anyhow::{anyhow, Error, Result};
+use serde_json::Value;
+use v8::{self, HandleScope, Local};
+use super::channel::Reply;
+use super::failure::failure;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub name:     String,
+    pub kind:     Kind,
+    pub function: Option<String>,
+    pub length:   Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Function,
+    AsyncFunction,
+    Generator,
+    Value,
+}
+
+pub struct List {
+    pub sender: Reply<Result<Vec<Entry>>>,
+}
+
+pub struct Read {
+    pub export: String,
+    pub sender: Reply<Result<Value>>,
+}
+
+pub fn list(
+    scope:   &mut HandleScope,
+    exports: Local<v8::Object>,
+    List { sender }: List,
+) -> Result<()> {
+    let scope  = &mut v8::TryCatch::new(scope);
+    let result = entries(scope, exports).ok_or_else(|| failure(scope));
+    sender.send(result).or(Ok(()))
+}
+
+pub fn read(
+    scope:   &mut HandleScope,
+    exports: Local<v8::Object>,
+    Read { export, sender }: Read,
+) -> Result<()> {
+    let scope  = &mut v8::TryCatch::new(scope);
+    let result = match value(scope, exports, &export) {
+        Some(value) if value.is_function() => Err(anyhow!("{export} is a function")),
+        Some(value)                        => serde_v8::from_v8(scope, value).map_err(Error::from),
+        None if scope.has_caught()         => Err(failure(scope)),
+        None                               => Err(anyhow!("{export} is not exported")),
+    };
+    sender.send(result).or(Ok(()))
+}
+
+fn entries(
+    scope:   &mut v8::TryCatch<HandleScope>,
+    exports: Local<v8::Object>,
+) -> Option<Vec<Entry>> {
+    let names = exports.get_own_property_names(scope)?;
+    let mut entries = Vec::new();
+
+    for index in 0..names.length() {
+        let name  = names.get_index(scope, index)?;
+        let value = exports.get(scope, name)?;
+        let name  = name.to_rust_string_lossy(scope);
+
+        let func = match Local::<v8::Function>::try_from(value) {
+            Ok(func) => func,
+            Err(_)   => {
+                entries.push(Entry { name, kind: Kind::Value, function: None, length: None });
+                continue;
+            },
+        };
+
+        let kind = if value.is_generator_function() {
+            Kind::Generator
+        } else if value.is_async_function() {
+            Kind::AsyncFunction
+        } else {
+            Kind::Function
+        };
+
+        let length = v8::String::new(scope, "length")?;
+        let length = func.get(scope, length.into())?.uint32_value(scope);
+
+        entries.push(Entry {
+            name:     name,
+            kind:     kind,
+            function: Some(func.get_name(scope).to_rust_string_lossy(scope)),
+            length:   length,
+        });
+    }
+
+    Some(entries)
+}
+
+fn value<'s>(
+    scope:   &mut v8::TryCatch<HandleScope<'s>>,
+    exports: Local<v8::Object>,
+    export:  &str,
+) -> Option<Local<'s, v8::Value>> {
+    let name = v8::String::new(scope, export)?;
+    match exports.has_own_property(scope, name.into())? {
+        true  => exports.get(scope, name.into()),
+        false => None,
+    }
+}
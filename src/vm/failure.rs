@@ -0,0 +1,245 @@
+use std::fmt::{self, Display, Formatter, Write};
+use anyhow::{anyhow, Error};
+use serde_json::Value;
+use v8::{self, HandleScope, Local};
+use super::srcmap::SourceMaps;
+
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub message:  String,
+    pub location: Option<Location>,
+    pub stack:    Vec<Frame>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub script: String,
+    pub line:   usize,
+    pub column: usize,
+    pub length: usize,
+    pub source: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub script:   String,
+    pub line:     usize,
+    pub column:   usize,
+}
+
+impl Failure {
+    pub fn exception<'s>(scope: &mut HandleScope<'s>, value: Local<'s, v8::Value>) -> Self {
+        let stack   = stack(scope, value);
+        let message = match cause(scope, value) {
+            Value::String(s) => s,
+            value            => value.to_string(),
+        };
+
+        Self {
+            message:  message,
+            location: None,
+            stack:    stack,
+        }
+    }
+}
+
+pub fn failure(scope: &mut v8::TryCatch<v8::HandleScope>) -> Error {
+    let exception = scope.exception();
+    let stack     = match exception {
+        Some(exception) => stack(scope, exception),
+        None            => Vec::new(),
+    };
+
+    if let Some((message, location)) = message(scope) {
+        return Failure { message, location: Some(location), stack }.into();
+    }
+
+    match exception {
+        Some(e) => Failure {
+            message:  e.to_rust_string_lossy(scope),
+            location: None,
+            stack:    stack,
+        }.into(),
+        None => anyhow!("no exception or message"),
+    }
+}
+
+pub fn prepare<'s>(
+    scope:  &mut HandleScope<'s>,
+    error:  Local<'s, v8::Value>,
+    sites:  Local<'s, v8::Array>,
+) -> Local<'s, v8::Value> {
+    let mut stack = error.to_rust_string_lossy(scope);
+
+    for index in 0..sites.length() {
+        let site = match sites.get_index(scope, index) {
+            Some(site) => site,
+            None       => continue,
+        };
+
+        let site = match Local::<v8::Object>::try_from(site) {
+            Ok(site) => site,
+            Err(_)   => continue,
+        };
+
+        let function = invoke(scope, site, "getFunctionName").filter(|v| v.is_string());
+        let script   = invoke(scope, site, "getFileName").filter(|v| v.is_string());
+        let line     = invoke(scope, site, "getLineNumber");
+        let column   = invoke(scope, site, "getColumnNumber");
+
+        let frame = Frame {
+            function: function.map(|f| f.to_rust_string_lossy(scope)),
+            script:   script.map(|s| s.to_rust_string_lossy(scope)).unwrap_or_default(),
+            line:     line.and_then(|l| l.integer_value(scope)).unwrap_or_default() as usize,
+            column:   column.and_then(|c| c.integer_value(scope)).unwrap_or_default() as usize,
+        };
+
+        let frame = remap(scope, frame);
+
+        write!(&mut stack, "\n    at {frame}").unwrap();
+    }
+
+    v8::String::new(scope, &stack).unwrap().into()
+}
+
+fn cause<'a>(scope: &'a mut HandleScope, mut value: Local<'a, v8::Value>) -> Value {
+    if let Ok(object) = v8::Local::<v8::Object>::try_from(value) {
+        let context = scope.get_current_context();
+        let global  = context.global(scope);
+
+        let name  = v8::String::new(scope, "Error").unwrap();
+        let error = global.get(scope, name.into()).unwrap();
+
+        if let Ok(error) = v8::Local::<v8::Object>::try_from(error) {
+            if let Some(true) = object.instance_of(scope, error) {
+                let name = v8::String::new(scope, "toString").unwrap();
+                let func = object.get(scope, name.into()).unwrap();
+                if let Ok(func) = v8::Local::<v8::Function>::try_from(func) {
+                    value = func.call(scope, object.into(), &[]).unwrap();
+                }
+            }
+        }
+    }
+    serde_v8::from_v8(scope, value).unwrap()
+}
+
+fn message(scope: &mut v8::TryCatch<v8::HandleScope>) -> Option<(String, Location)> {
+    let msg    = scope.message()?;
+    let text   = msg.get(scope).to_rust_string_lossy(scope);
+    let script = msg.get_script_resource_name(scope)?.to_rust_string_lossy(scope);
+    let source = msg.get_source_line(scope)?.to_rust_string_lossy(scope);
+    let line   = msg.get_line_number(scope)?;
+    let column = msg.get_start_column();
+    let length = msg.get_end_column().saturating_sub(column);
+
+    let mapped = scope.get_slot::<SourceMaps>().and_then(|maps| {
+        maps.lookup(&script, line.saturating_sub(1), column)
+    });
+
+    let location = match mapped {
+        Some(mapped) => Location {
+            script: mapped.source,
+            line:   mapped.line + 1,
+            column: mapped.column,
+            length: length,
+            source: mapped.content,
+        },
+        None => Location {
+            script: script,
+            line:   line,
+            column: column,
+            length: length,
+            source: Some(source),
+        },
+    };
+
+    Some((text, location))
+}
+
+fn stack(scope: &mut HandleScope, exception: Local<v8::Value>) -> Vec<Frame> {
+    let trace = match v8::Exception::get_stack_trace(scope, exception) {
+        Some(trace) => trace,
+        None        => return Vec::new(),
+    };
+
+    (0..trace.get_frame_count()).filter_map(|index| {
+        let frame    = trace.get_frame(scope, index)?;
+        let function = frame.get_function_name(scope).map(|s| s.to_rust_string_lossy(scope));
+        let script   = frame.get_script_name(scope).map(|s| s.to_rust_string_lossy(scope));
+
+        Some(remap(scope, Frame {
+            function: function.filter(|f| !f.is_empty()),
+            script:   script.unwrap_or_default(),
+            line:     frame.get_line_number(),
+            column:   frame.get_column(),
+        }))
+    }).collect()
+}
+
+fn remap(scope: &mut HandleScope, frame: Frame) -> Frame {
+    let line   = frame.line.saturating_sub(1);
+    let column = frame.column.saturating_sub(1);
+
+    let mapped = scope.get_slot::<SourceMaps>().and_then(|maps| {
+        maps.lookup(&frame.script, line, column)
+    });
+
+    match mapped {
+        Some(mapped) => Frame {
+            function: mapped.name.or(frame.function),
+            script:   mapped.source,
+            line:     mapped.line + 1,
+            column:   mapped.column + 1,
+        },
+        None => frame,
+    }
+}
+
+fn invoke<'s>(
+    scope:  &mut HandleScope<'s>,
+    object: Local<'s, v8::Object>,
+    method: &str,
+) -> Option<Local<'s, v8::Value>> {
+    let name = v8::String::new(scope, method)?;
+    let func = object.get(scope, name.into())?;
+    let func = Local::<v8::Function>::try_from(func).ok()?;
+    func.call(scope, object.into(), &[])
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{}\n{}", self.message, location),
+            None           => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self { script, line, column, length, .. } = self;
+
+        writeln!(f, "{:>4}--> {}:{}:{}", "", script, line, column)?;
+
+        if let Some(source) = &self.source {
+            writeln!(f, "{:>4} |   ", "")?;
+            writeln!(f, "{:>4} | {}", line, source)?;
+            writeln!(f, "{:>4} | {:>3$}{:^>4$}", "", "", "", *column, *length)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self { script, line, column, .. } = self;
+        match &self.function {
+            Some(function) => write!(f, "{function} ({script}:{line}:{column})"),
+            None           => write!(f, "{script}:{line}:{column}"),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
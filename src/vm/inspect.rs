@@ -1,4 +1,7 @@
-use v8::{Isolate, UniqueRef};
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use v8::{Isolate, UniquePtr, UniqueRef};
 use v8::inspector::*;
 use tracing::{event, Level};
 
@@ -6,6 +9,17 @@ pub struct Inspector {
     base: V8InspectorClientBase,
 }
 
+pub struct Session {
+    session: UniqueRef<V8InspectorSession>,
+    channel: Box<Responses>,
+    counter: i32,
+}
+
+struct Responses {
+    base:    ChannelBase,
+    pending: HashMap<i32, String>,
+}
+
 impl Inspector {
     pub fn new() -> Self {
         Self {
@@ -18,6 +32,68 @@ impl Inspector {
     }
 }
 
+impl Session {
+    pub fn connect(inspector: &mut V8Inspector) -> Self {
+        let mut channel = Box::new(Responses {
+            base:    ChannelBase::new::<Responses>(),
+            pending: HashMap::new(),
+        });
+
+        let state   = StringView::empty();
+        let trust   = V8InspectorClientTrustLevel::FullyTrusted;
+        let session = inspector.connect(1, &mut *channel, state, trust);
+
+        Self {
+            session: session,
+            channel: channel,
+            counter: 0,
+        }
+    }
+
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.counter += 1;
+
+        let id      = self.counter;
+        let message = json!({ "id": id, "method": method, "params": params });
+        let message = message.to_string();
+
+        self.session.dispatch_protocol_message(StringView::from(message.as_bytes()));
+
+        let response = match self.channel.pending.remove(&id) {
+            Some(response) => response,
+            None           => return Err(anyhow!("{method}: no response")),
+        };
+
+        let mut response = serde_json::from_str::<Value>(&response)?;
+        match response.get("error") {
+            Some(error) => Err(anyhow!("{method}: {}", error["message"])),
+            None        => Ok(response["result"].take()),
+        }
+    }
+}
+
+impl ChannelImpl for Responses {
+    fn base(&self) -> &ChannelBase {
+        &self.base
+    }
+
+    fn base_mut(&mut self) -> &mut ChannelBase {
+        &mut self.base
+    }
+
+    fn send_response(&mut self, call_id: i32, message: UniquePtr<StringBuffer>) {
+        if let Some(message) = message.as_ref() {
+            self.pending.insert(call_id, message.string().to_string());
+        }
+    }
+
+    fn send_notification(&mut self, _message: UniquePtr<StringBuffer>) {
+    }
+
+    fn flush_protocol_notifications(&mut self) {
+    }
+}
+
 impl V8InspectorClientImpl for Inspector {
     fn base(&self) -> &V8InspectorClientBase {
         &self.base
@@ -32,18 +108,18 @@ impl V8InspectorClientImpl for Inspector {
         _context_group_id: i32,
         level:            i32,
         message:           &StringView,
-        _url:              &StringView,
+        url:               &StringView,
         _line_number:      u32,
         _column_number:    u32,
         _stack_trace:      &mut V8StackTrace,
     ) {
         match level {
-            INFO  => event!(target: "<script>", Level::INFO,  "{}", message),
-            DEBUG => event!(target: "<script>", Level::DEBUG, "{}", message),
-            TRACE => event!(target: "<script>", Level::TRACE, "{}", message),
-            ERROR => event!(target: "<script>", Level::ERROR, "{}", message),
-            WARN  => event!(target: "<script>", Level::WARN,  "{}", message),
-            _     => event!(target: "<script>", Level::INFO,  "{}", message),
+            INFO  => event!(target: "<script>", Level::INFO,  script = %url, "{}", message),
+            DEBUG => event!(target: "<script>", Level::DEBUG, script = %url, "{}", message),
+            TRACE => event!(target: "<script>", Level::TRACE, script = %url, "{}", message),
+            ERROR => event!(target: "<script>", Level::ERROR, script = %url, "{}", message),
+            WARN  => event!(target: "<script>", Level::WARN,  script = %url, "{}", message),
+            _     => event!(target: "<script>", Level::INFO,  script = %url, "{}", message),
         }
     }
 }
@@ -1,24 +1,59 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::future::poll_fn;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
 use std::sync::Arc;
 use std::thread::{spawn, JoinHandle};
+use std::time::{Duration, Instant};
 use anyhow::{anyhow, Result};
-use crossbeam_channel::{unbounded, Sender, Receiver};
+use crossbeam_channel::{unbounded, Sender, Receiver, RecvTimeoutError, TryRecvError};
 use v8::{self, inspector::StringView};
 use serde_json::Value;
-use tracing::{debug, error};
+use tracing::{debug, error, warn, Span};
 use super::adjunct::Adjunct;
-use super::channel::{oneshot, Rx};
-use super::context::{Context, Call, Export, Find};
-use super::inspect::Inspector;
+use super::channel::{block, oneshot, reply, timeout, Reply, Response, Rx};
+use super::context::{Context, Call, Eval, Export, Find, Get, Reload, Set, Snapshot, Stats};
+use super::coverage::{self, Collect, Coverage};
+use super::exports::{Entry, List, Read};
+use super::failure;
+use super::inspect::{Inspector, Session};
+use super::metrics::Metrics;
+#[cfg(feature = "metrics")]
+use super::metrics::Recorder;
 use super::promise::{Promise, Promises};
+use super::queue::Priority;
+use super::realm::Realm;
+use super::srcmap::{SourceMap, SourceMaps};
+use super::state::{State, Status};
+use super::supervise::{Backoff, Health};
+use super::trace::Trace;
 
 pub struct Machine {
-    module: String,
-    extra:  Vec<Box<dyn Adjunct>>,
+    name:     String,
+    module:   String,
+    srcmap:   Option<String>,
+    extra:    Vec<Box<dyn Adjunct>>,
+    factory:  Option<Factory>,
+    coverage: bool,
+    pristine: bool,
+    queue:    Option<usize>,
+    weights:  HashMap<String, u32>,
+    limit:    Option<usize>,
+    heap:     Option<usize>,
+    drain:    Duration,
+    backoff:  Option<Backoff>,
+    realms:   Vec<(String, Realm)>,
+    globals:  Vec<(String, Value)>,
+    #[cfg(feature = "metrics")]
+    recorder: Option<Arc<dyn Recorder>>,
 }
 
 #[derive(Clone)]
 pub struct Handle {
     sender: Sender<Command>,
+    state:  Arc<State>,
 }
 
 pub struct Guard {
@@ -28,8 +63,11 @@ pub struct Guard {
 
 #[derive(Clone)]
 pub struct Function {
-    export: Export,
-    handle: Handle,
+    export:   Export,
+    handle:   Handle,
+    priority: Priority,
+    tenant:   Arc<String>,
+    trace:    Option<Trace>,
 }
 
 pub trait Args {
@@ -37,47 +75,167 @@ pub trait Args {
 }
 
 struct Thread {
-    module:   String,
+    name:     String,
+    module:   RefCell<String>,
+    srcmap:   RefCell<Option<String>>,
     extra:    Vec<Box<dyn Adjunct>>,
+    factory:  Option<Factory>,
+    coverage: bool,
+    pristine: bool,
+    limit:    Option<usize>,
+    heap:     Option<usize>,
+    drain:    Duration,
+    backoff:  Option<Backoff>,
+    realms:   Vec<(String, Realm)>,
+    globals:  Vec<(String, Value)>,
     receiver: Receiver<Command>,
     handle:   Handle,
 }
 
+type Factory = Box<dyn Fn() -> Vec<Box<dyn Adjunct>> + Send>;
+
 pub enum Command {
-    Call(Call),
+    Call,
     Find(Find),
+    Stats(Stats),
+    Snapshot(Snapshot),
+    Coverage(Collect),
+    List(List),
+    Read(Read),
+    Reload(Reload),
+    Get(Get),
+    Set(Set),
+    Eval(Eval),
     Done(Promise),
+    Cancel(u64),
+    Ping(Reply<()>),
     Tick,
-    Stop,
+    Stop(Option<Reply<()>>),
 }
 
 impl Machine {
     pub fn new(module: String) -> Self {
         let extra = Vec::new();
-        Self { module, extra }
+        Self {
+            name:     "<script>".to_owned(),
+            module:   module,
+            srcmap:   None,
+            extra:    extra,
+            factory:  None,
+            coverage: false,
+            pristine: false,
+            queue:    None,
+            weights:  HashMap::new(),
+            limit:    None,
+            heap:     None,
+            drain:    DRAIN_TIMEOUT,
+            backoff:  None,
+            realms:   Vec::new(),
+            globals:  Vec::new(),
+            #[cfg(feature = "metrics")]
+            recorder: None,
+        }
+    }
+
+    pub fn name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn source_map(&mut self, srcmap: String) {
+        self.srcmap = Some(srcmap);
     }
 
     pub fn extend<T: Adjunct>(&mut self, adjunct: Box<T>) {
         self.extra.push(adjunct);
     }
 
+    pub fn factory<F>(&mut self, factory: F)
+    where
+        F: Fn() -> Vec<Box<dyn Adjunct>> + Send + 'static,
+    {
+        self.factory = Some(Box::new(factory));
+    }
+
+    pub fn coverage(&mut self, enable: bool) {
+        self.coverage = enable;
+    }
+
+    pub fn pristine(&mut self, enable: bool) {
+        self.pristine = enable;
+    }
+
+    pub fn queue(&mut self, capacity: usize) {
+        self.queue = Some(capacity);
+    }
+
+    pub fn weight(&mut self, tenant: String, weight: u32) {
+        self.weights.insert(tenant, weight);
+    }
+
+    pub fn in_flight(&mut self, limit: usize) {
+        self.limit = Some(limit);
+    }
+
+    pub fn heap_limit(&mut self, bytes: usize) {
+        self.heap = Some(bytes);
+    }
+
+    pub fn drain(&mut self, timeout: Duration) {
+        self.drain = timeout;
+    }
+
+    pub fn supervise(&mut self, backoff: Backoff) {
+        self.backoff = Some(backoff);
+    }
+
+    pub fn realm(&mut self, name: String, realm: Realm) {
+        self.realms.push((name, realm));
+    }
+
+    // defined read-only and non-deletable, neither scripts nor set_global
+    // can replace them
+    pub fn global(&mut self, name: String, value: Value) {
+        self.globals.push((name, value));
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&mut self, recorder: Arc<dyn Recorder>) {
+        self.recorder = Some(recorder);
+    }
+
     pub fn exec(self) -> (Handle, Guard) {
         let (sender, receiver) = unbounded();
 
-        let handle = Handle { sender };
+        #[cfg(feature = "metrics")]
+        let metrics = match self.recorder {
+            Some(recorder) => Metrics::new(&self.name, recorder),
+            None           => Metrics::default(),
+        };
+
+        #[cfg(not(feature = "metrics"))]
+        let metrics = Metrics::default();
+
+        let state  = Arc::new(State::new(self.queue, self.weights, metrics));
+        let handle = Handle { sender, state };
         let thread = Thread {
-            module:   self.module,
+            name:     self.name,
+            module:   RefCell::new(self.module),
+            srcmap:   RefCell::new(self.srcmap),
             extra:    self.extra,
+            factory:  self.factory,
+            coverage: self.coverage,
+            pristine: self.pristine,
+            limit:    self.limit,
+            heap:     self.heap,
+            drain:    self.drain,
+            backoff:  self.backoff,
+            realms:   self.realms,
+            globals:  self.globals,
             receiver: receiver,
             handle:   handle.clone(),
         };
 
-        let thread = spawn(move || {
-            match thread.exec() {
-                Ok(()) => debug!("machine finished"),
-                Err(e) => error!("machine failed: {e:?}"),
-            }
-        });
+        let thread = spawn(move || thread.run());
 
         let guard  = Guard {
             handle: handle.clone(),
@@ -90,17 +248,166 @@ impl Machine {
 
 impl Handle {
     pub fn find(&self, export: &str) -> Result<Function> {
-        let (sender, receiver) = unbounded();
-        let export = Arc::new(export.to_owned());
-        let handle = self.clone();
+        let export = self.lookup(None, export)?.recv()??;
+        Ok(self.function(export))
+    }
 
-        self.send(Command::Find(Find {
-            export: export.clone(),
-            sender: sender,
-        }))?;
+    pub async fn find_async(&self, export: &str) -> Result<Function> {
+        let export = self.lookup(None, export)?.await??;
+        Ok(self.function(export))
+    }
+
+    pub fn find_in(&self, realm: &str, export: &str) -> Result<Function> {
+        let export = self.lookup(Some(realm), export)?.recv()??;
+        Ok(self.function(export))
+    }
+
+    pub async fn find_in_async(&self, realm: &str, export: &str) -> Result<Function> {
+        let export = self.lookup(Some(realm), export)?.await??;
+        Ok(self.function(export))
+    }
+
+    pub fn heap_stats(&self) -> Result<v8::HeapStatistics> {
+        self.request(|sender| Command::Stats(Stats { sender }))?.recv()
+    }
+
+    pub async fn heap_stats_async(&self) -> Result<v8::HeapStatistics> {
+        self.request(|sender| Command::Stats(Stats { sender }))?.await
+    }
+
+    pub fn heap_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.snapshot(path.as_ref())?.recv()?
+    }
+
+    pub async fn heap_snapshot_async<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.snapshot(path.as_ref())?.await?
+    }
+
+    pub fn coverage(&self) -> Result<Coverage> {
+        self.request(|sender| Command::Coverage(Collect { sender }))?.recv()?
+    }
+
+    pub async fn coverage_async(&self) -> Result<Coverage> {
+        self.request(|sender| Command::Coverage(Collect { sender }))?.await?
+    }
+
+    pub fn exports(&self) -> Result<Vec<Entry>> {
+        self.request(|sender| Command::List(List { sender }))?.recv()?
+    }
+
+    pub async fn exports_async(&self) -> Result<Vec<Entry>> {
+        self.request(|sender| Command::List(List { sender }))?.await?
+    }
+
+    pub fn export(&self, export: &str) -> Result<Value> {
+        self.read(export)?.recv()?
+    }
+
+    pub async fn export_async(&self, export: &str) -> Result<Value> {
+        self.read(export)?.await?
+    }
+
+    // get and set only reach properties of globalThis, module-level let
+    // and const bindings are not visible to them. set assigns a plain
+    // writable property and fails for globals defined on the Machine
+    pub fn get_global(&self, name: &str) -> Result<Value> {
+        let name = name.to_owned();
+        self.request(|sender| Command::Get(Get { name, sender }))?.recv()?
+    }
+
+    pub async fn get_global_async(&self, name: &str) -> Result<Value> {
+        let name = name.to_owned();
+        self.request(|sender| Command::Get(Get { name, sender }))?.await?
+    }
+
+    pub fn set_global(&self, name: &str, value: Value) -> Result<()> {
+        let name = name.to_owned();
+        self.request(|sender| Command::Set(Set { name, value, sender }))?.recv()?
+    }
+
+    pub async fn set_global_async(&self, name: &str, value: Value) -> Result<()> {
+        let name = name.to_owned();
+        self.request(|sender| Command::Set(Set { name, value, sender }))?.await?
+    }
+
+    pub fn eval(&self, source: &str) -> Result<Value> {
+        self.evaluate(source)?.recv()
+    }
+
+    pub async fn eval_async(&self, source: &str) -> Result<Value> {
+        self.evaluate(source)?.await
+    }
+
+    pub fn reload(&self, module: String) -> Result<()> {
+        self.request(|sender| Command::Reload(Reload { module, sender }))?.recv()?
+    }
+
+    pub async fn reload_async(&self, module: String) -> Result<()> {
+        self.request(|sender| Command::Reload(Reload { module, sender }))?.await?
+    }
+
+    pub fn stop(&self) {
+        if let Ok(response) = self.request(|reply| Command::Stop(Some(reply))) {
+            let _ = response.recv();
+        }
+    }
 
-        let export = receiver.recv()??;
-        Ok(Function { export, handle })
+    pub async fn stop_async(&self) {
+        if let Ok(response) = self.request(|reply| Command::Stop(Some(reply))) {
+            let _ = response.await;
+        }
+    }
+
+    pub fn ping(&self, limit: Duration) -> Result<Duration> {
+        let start    = Instant::now();
+        let response = self.request(Command::Ping)?;
+
+        match response.recv_timeout(limit)? {
+            Some(()) => Ok(start.elapsed()),
+            None     => Err(anyhow!("ping timed out")),
+        }
+    }
+
+    pub async fn ping_async(&self, limit: Duration) -> Result<Duration> {
+        let start    = Instant::now();
+        let response = self.request(Command::Ping)?;
+
+        match timeout(response, limit).await {
+            Some(result) => result.map(|()| start.elapsed()),
+            None         => Err(anyhow!("ping timed out")),
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.state.status()
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.state.queue.depth()
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.state.in_flight()
+    }
+
+    pub fn pending_promises(&self) -> usize {
+        self.state.promises()
+    }
+
+    pub fn health(&self) -> Health {
+        self.state.health()
+    }
+
+    pub fn restarts(&self) -> usize {
+        self.state.restarts()
+    }
+
+    pub(super) fn track(&self, pending: usize) {
+        self.state.track(pending);
+    }
+
+    pub(super) fn next(&self) -> u64 {
+        self.state.next()
     }
 
     pub fn done(&self, promise: Promise) -> Result<()> {
@@ -111,6 +418,63 @@ impl Handle {
         self.send(Command::Tick)
     }
 
+    fn cancel(&self, call: u64) {
+        self.state.cancel(call);
+        let _ = self.send(Command::Cancel(call));
+    }
+
+    fn function(&self, export: Export) -> Function {
+        Function {
+            export:   export,
+            handle:   self.clone(),
+            priority: Priority::default(),
+            tenant:   Arc::default(),
+            trace:    None,
+        }
+    }
+
+    fn lookup(&self, realm: Option<&str>, export: &str) -> Result<Response<Result<Export>>> {
+        let realm  = realm.map(|realm| Arc::new(realm.to_owned()));
+        let export = Arc::new(export.to_owned());
+        self.request(|sender| Command::Find(Find { realm, export, sender }))
+    }
+
+    fn evaluate(&self, source: &str) -> Result<Rx> {
+        let id = self.next();
+        let (sender, mut rx) = oneshot();
+        self.send(Command::Eval(Eval {
+            id:     id,
+            source: source.to_owned(),
+            sender: sender,
+        }))?;
+
+        let handle = self.clone();
+        rx.on_cancel(move || handle.cancel(id));
+
+        Ok(rx)
+    }
+
+    fn read(&self, export: &str) -> Result<Response<Result<Value>>> {
+        let export = export.to_owned();
+        self.request(|sender| Command::Read(Read { export, sender }))
+    }
+
+    fn snapshot(&self, path: &Path) -> Result<Response<Result<()>>> {
+        self.request(|sender| Command::Snapshot(Snapshot {
+            path:   path.to_owned(),
+            sender: sender,
+        }))
+    }
+
+    fn request<T, F>(&self, command: F) -> Result<Response<T>>
+    where
+        F: FnOnce(Reply<T>) -> Command,
+    {
+        let (sender, response) = reply();
+        self.send(command(sender))?;
+        Ok(response)
+    }
+
     fn send(&self, cmd: Command) -> Result<()> {
         match self.sender.send(cmd) {
             Ok(()) => Ok(()),
@@ -120,25 +484,143 @@ impl Handle {
 }
 
 impl Function {
+    pub fn priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    pub fn tenant(&mut self, tenant: String) {
+        self.tenant = Arc::new(tenant);
+    }
+
+    pub fn trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
     pub fn call<A: Args>(&self, args: A) -> Result<Rx> {
-        let (tx, rx) = oneshot();
-        let export = self.export.clone();
-        self.handle.send(Command::Call(Call {
-            export: export,
+        let (call, rx) = self.prepare(args);
+        let (id, call) = (call.id, &mut Some(call));
+        let queue = &self.handle.state.queue;
+
+        let mut push = poll_fn(|cx| queue.poll_push(call, self.priority, &self.tenant, cx));
+        block(&mut push, None).unwrap_or_else(|| unreachable!())?;
+        self.submit(id, rx)
+    }
+
+    pub async fn call_async<A: Args>(&self, args: A) -> Result<Value> {
+        let (call, rx) = self.prepare(args);
+        let (id, call) = (call.id, &mut Some(call));
+        let queue = &self.handle.state.queue;
+
+        poll_fn(|cx| queue.poll_push(call, self.priority, &self.tenant, cx)).await?;
+        self.submit(id, rx)?.await
+    }
+
+    pub fn try_call<A: Args>(&self, args: A) -> Result<Rx> {
+        let (call, rx) = self.prepare(args);
+        let id = call.id;
+
+        self.handle.state.queue.push(call, self.priority, &self.tenant)?;
+        self.submit(id, rx)
+    }
+
+    fn prepare<A: Args>(&self, args: A) -> (Call, Rx) {
+        let (mut tx, rx) = oneshot();
+        let state = self.handle.state.clone();
+        let start = Instant::now();
+        tx.on_send(move || state.served(start.elapsed()));
+
+        let call = Call {
+            id:     self.handle.state.next(),
+            export: self.export.clone(),
             args:   args.args(),
             sender: tx,
-        }))?;
+            start:  start,
+            trace:  self.trace.map_or_else(Trace::new, |trace| trace.child()),
+            span:   Span::current(),
+        };
+        (call, rx)
+    }
+
+    fn submit(&self, id: u64, mut rx: Rx) -> Result<Rx> {
+        self.handle.send(Command::Call)?;
+
+        let handle = self.handle.clone();
+        rx.on_cancel(move || handle.cancel(id));
+
         Ok(rx)
     }
 }
 
 impl Thread {
-    fn exec(self) -> Result<()> {
-        let Self { module, extra, receiver, handle } = self;
+    fn run(self) {
+        let mut delay = Duration::ZERO;
+
+        loop {
+            let start = Instant::now();
+            let error = match catch_unwind(AssertUnwindSafe(|| self.exec())) {
+                Ok(Ok(stop)) => return self.finish(Health::Stopped, stop),
+                Ok(Err(e))   => e,
+                Err(_)       => anyhow!("machine panicked"),
+            };
+
+            let backoff = match &self.backoff {
+                Some(backoff) => backoff,
+                None          => {
+                    error!(script = %self.name, "machine failed: {error:?}");
+                    return self.finish(Health::Failed, None);
+                },
+            };
+
+            delay = backoff.next(delay, start.elapsed());
+            warn!(script = %self.name, "machine failed, restarting in {delay:?}: {error:?}");
+
+            self.handle.state.set_health(Health::Restarting);
+            self.handle.track(0);
+
+            if let Err(stop) = self.pause(delay) {
+                return self.finish(Health::Stopped, stop);
+            }
+
+            self.handle.state.restarted();
+        }
+    }
+
+    fn exec(&self) -> Result<Option<Reply<()>>> {
+        let Self { name, extra, factory, coverage, pristine, heap, globals, handle, .. } = self;
+
+        let mut sources = SourceMaps::default();
+        let (module, srcmap) = load(name, &self.module.borrow(), &self.srcmap.borrow())?;
+        if let Some(srcmap) = srcmap {
+            sources.insert(name, srcmap);
+        }
 
-        let mut promises  = Promises::new(handle);
+        let realms = self.realms.iter().map(|(key, realm)| {
+            let (module, srcmap) = load(key, &realm.module, &realm.srcmap)?;
+            if let Some(srcmap) = srcmap {
+                sources.insert(key, srcmap);
+            }
+            Ok((key, module, &realm.extra))
+        }).collect::<Result<Vec<_>>>()?;
+
+        let fresh = factory.as_ref().map(|factory| factory()).unwrap_or_default();
+
+        let mut promises  = Promises::new(handle.clone());
+
+        let params = match heap {
+            Some(max) => v8::CreateParams::default().heap_limits(0, *max),
+            None      => v8::CreateParams::default(),
+        };
+
+        let mut isolate   = v8::Isolate::new(params);
+        if heap.is_some() {
+            let state = Arc::as_ptr(&handle.state) as *mut c_void;
+            isolate.add_near_heap_limit_callback(exhausted, state);
+        }
+        isolate.set_slot(sources);
+        isolate.set_capture_stack_trace_for_uncaught_exceptions(true, STACK_FRAMES);
+        isolate.set_prepare_stack_trace_callback(failure::prepare);
+        handle.state.attach(isolate.thread_safe_handle());
 
-        let mut isolate   = v8::Isolate::new(v8::CreateParams::default());
         let mut inspector = Inspector::new();
         let mut inspector = inspector.create(&mut isolate);
 
@@ -146,10 +628,15 @@ impl Thread {
         let global = v8::ObjectTemplate::new(scope);
         global.set_internal_field_count(1);
 
-        for adjunct in &extra {
+        for adjunct in extra.iter().chain(&fresh) {
             adjunct.install(scope, &global);
         }
 
+        let template = match pristine {
+            true  => Some(v8::Global::new(scope, global)),
+            false => None,
+        };
+
         let context   = v8::Context::new_from_template(scope, global);
         let mut scope = v8::ContextScope::new(scope, context);
 
@@ -159,30 +646,272 @@ impl Thread {
         let global = context.global(&mut scope);
         global.set_internal_field(0, promises.into());
 
-        let name = StringView::from(b"".as_slice());
-        inspector.context_created(context, 1, name);
+        let view = name.encode_utf16().collect::<Vec<_>>();
+        inspector.context_created(context, 1, StringView::from(&view[..]));
+
+        let mut session = match coverage {
+            true  => Some(Session::connect(&mut inspector)),
+            false => None,
+        };
+
+        if let Some(session) = &mut session {
+            coverage::start(session)?;
+        }
+
+        let metrics = handle.state.metrics.clone();
+        let mut context = Context::new(scope, name, &module, globals, metrics)?;
+        if let Some(template) = template {
+            context.pristine(template, name, &module, globals)?;
+        }
+        for (key, module, extra) in &realms {
+            context.realm(key, module, extra)?;
+        }
+        handle.state.set_health(Health::Running);
+
+        let result = self.serve(&mut context, session.as_mut(), module);
+        if result.is_err() && self.backoff.is_some() {
+            context.restart();
+        }
+        let stop = result?;
 
-        let mut context = Context::new(scope, &module)?;
+        match handle.pending_promises() {
+            0 => (),
+            n => warn!(script = %name, "machine stopped with {n} pending promises"),
+        }
+
+        Ok(stop)
+    }
+
+    fn serve(
+        &self,
+        context:     &mut Context,
+        mut session: Option<&mut Session>,
+        mut module:  String,
+    ) -> Result<Option<Reply<()>>> {
+        let Self { name, limit, drain, receiver, handle, .. } = self;
+        let mut reload  = None::<(Reload, Instant)>;
+
+        #[cfg(feature = "metrics")]
+        let mut sampled = None::<Instant>;
 
         loop {
-            match receiver.recv() {
-                Ok(Command::Call(call))    => context.call(call)?,
+            if handle.state.exhausted() {
+                return Err(anyhow!("heap limit reached"));
+            }
+
+            handle.state.set_in_flight(context.in_flight());
+            handle.state.set_compiles(context.compiles());
+
+            // swap the module only once every in-flight call has settled
+            if context.in_flight() == 0 {
+                if let Some(source) = reload.take().and_then(|(r, _)| self.reload(context, r)) {
+                    module = source;
+                }
+            }
+
+            // give up on a reload whose in-flight calls did not drain in time
+            if let Some((_, deadline)) = &reload {
+                if Instant::now() >= *deadline {
+                    let (Reload { sender, .. }, _) = reload.take().unwrap();
+                    let in_flight = context.in_flight();
+                    let error = anyhow!("reload timed out waiting for {in_flight} in-flight calls");
+                    let _ = sender.send(Err(error));
+                }
+            }
+
+            #[cfg(feature = "metrics")]
+            if sampled.map_or(true, |at| at.elapsed() >= HEAP_INTERVAL) {
+                handle.state.metrics.heap(context.heap());
+                sampled = Some(Instant::now());
+            }
+
+            // wake up for the next heap sample even when idle
+            #[cfg(feature = "metrics")]
+            let wake = sampled.map(|at| at + HEAP_INTERVAL);
+
+            #[cfg(not(feature = "metrics"))]
+            let wake = None;
+
+            let drained = reload.as_ref().map(|(_, deadline)| *deadline);
+            let wake    = wake.into_iter().chain(drained).min();
+
+            let open = reload.is_none() && limit.map_or(true, |limit| handle.in_flight() < limit);
+            let next = || match open {
+                true  => handle.state.queue.pop(),
+                false => None,
+            };
+
+            // internal commands are always handled before the next queued call
+            let command = match receiver.try_recv() {
+                Ok(command)              => Ok(command),
+                Err(TryRecvError::Empty) => match next() {
+                    Some(call) => {
+                        handle.state.metrics.queue(call.start.elapsed());
+                        context.call(call, &handle.state)?;
+                        context.tick();
+                        continue;
+                    },
+                    None => recv(receiver, wake),
+                },
+                Err(e) => Err(e),
+            };
+
+            match command {
+                Ok(Command::Call)          => (),
                 Ok(Command::Find(find))    => context.find(find)?,
+                Ok(Command::Stats(stats))  => context.stats(stats)?,
+                Ok(Command::Snapshot(s))   => context.snapshot(s)?,
+                Ok(Command::Coverage(c))   => {
+                    coverage::collect(session.as_deref_mut(), name, &module, c)?
+                },
+                Ok(Command::List(list))    => context.list(list)?,
+                Ok(Command::Read(read))    => context.read(read)?,
+                Ok(Command::Get(get))      => context.get(get)?,
+                Ok(Command::Set(set))      => context.set(set)?,
+                Ok(Command::Eval(eval))    => context.eval(eval, &handle.state)?,
+                Ok(Command::Reload(r))     => {
+                    if let Some((Reload { sender, .. }, _)) = reload.replace((r, Instant::now() + *drain)) {
+                        let _ = sender.send(Err(anyhow!("reload superseded")));
+                    }
+                },
                 Ok(Command::Done(promise)) => context.done(promise)?,
+                Ok(Command::Cancel(call))  => {
+                    // a call still queued never starts, drop it rather than run it
+                    if let Some(Call { sender, .. }) = handle.state.queue.remove(call) {
+                        sender.send(Err(anyhow!("call cancelled")));
+                    }
+                    handle.state.forget(call);
+                    context.cancel(call)?
+                },
+                Ok(Command::Ping(reply))   => {
+                    let _ = reply.send(());
+                },
                 Ok(Command::Tick)          => (),
-                Ok(Command::Stop) | Err(_) => break,
+                Ok(Command::Stop(reply))   => return Ok(reply),
+                Err(_)                     => return Ok(None),
             }
             context.tick();
         }
+    }
+
+    fn reload(&self, context: &mut Context, Reload { module, sender }: Reload) -> Option<String> {
+        let result = load(&self.name, &module, &None).and_then(|(source, srcmap)| {
+            context.reload(&self.name, &source)?;
+            Ok((source, srcmap))
+        });
+
+        let (source, srcmap) = match result {
+            Ok(loaded) => loaded,
+            Err(e)     => {
+                warn!(script = %self.name, "reload failed: {e:?}");
+                let _ = sender.send(Err(e));
+                return None;
+            },
+        };
+
+        if let Some(sources) = context.scope.get_slot_mut::<SourceMaps>() {
+            match srcmap {
+                Some(srcmap) => sources.insert(&self.name, srcmap),
+                None         => sources.remove(&self.name),
+            }
+        }
+
+        // keep the new version across supervised restarts
+        *self.module.borrow_mut() = module;
+        *self.srcmap.borrow_mut() = None;
+
+        let _ = sender.send(Ok(()));
+        Some(source)
+    }
+
+    fn pause(&self, delay: Duration) -> Result<(), Option<Reply<()>>> {
+        let deadline = Instant::now() + delay;
+        let mut deferred = Vec::new();
+
+        // keep commands that arrive while waiting for the next machine
+        let result = loop {
+            match self.receiver.recv_deadline(deadline) {
+                Ok(Command::Stop(reply)) => break Err(reply),
+                Ok(command)              => deferred.push(command),
+                Err(_)                   => break Ok(()),
+            }
+        };
+
+        for command in deferred {
+            let _ = self.handle.send(command);
+        }
+
+        result
+    }
+
+    fn finish(&self, health: Health, stop: Option<Reply<()>>) {
+        debug!(script = %self.name, "machine finished");
+
+        self.handle.state.set_health(health);
+        self.handle.state.queue.close();
+
+        if let Some(reply) = stop {
+            let _ = reply.send(());
+        }
+    }
+}
+
+fn recv(receiver: &Receiver<Command>, deadline: Option<Instant>) -> Result<Command, TryRecvError> {
+    let command = match deadline {
+        Some(deadline) => receiver.recv_deadline(deadline),
+        None           => receiver.recv().map_err(RecvTimeoutError::from),
+    };
+
+    match command {
+        Ok(command)                         => Ok(command),
+        Err(RecvTimeoutError::Timeout)      => Ok(Command::Tick),
+        Err(RecvTimeoutError::Disconnected) => Err(TryRecvError::Disconnected),
+    }
+}
+
+// stop the isolate before V8 aborts the process, the extra headroom only
+// lets the running script unwind
+extern "C" fn exhausted(data: *mut c_void, current: usize, _initial: usize) -> usize {
+    let state = unsafe { &*(data as *const State) };
+    state.exhaust();
+    current * 2
+}
+
+#[cfg_attr(not(feature = "typescript"), allow(unused_variables))]
+fn load(name: &str, module: &str, srcmap: &Option<String>) -> Result<(String, Option<SourceMap>)> {
+    let module = module.to_owned();
+    let srcmap = srcmap.clone();
+
+    #[cfg(feature = "typescript")]
+    let (module, srcmap) = super::typescript::transpile(name, module, srcmap)?;
+
+    let srcmap = match srcmap {
+        Some(srcmap) => Some(SourceMap::parse(&srcmap)?),
+        None         => SourceMap::inline(&module).transpose()?,
+    };
+
+    Ok((module, srcmap))
+}
+
+const STACK_FRAMES: i32 = 16;
+
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[cfg(feature = "metrics")]
+const HEAP_INTERVAL: Duration = Duration::from_secs(10);
 
-        Ok(())
+impl Guard {
+    pub async fn shutdown(mut self) {
+        self.handle.stop_async().await;
+        // thread has already exited, detach it rather than join
+        self.thread = None;
     }
 }
 
 impl Drop for Guard {
     fn drop(&mut self) {
         if let Some(handle) = self.thread.take() {
-            let _ = self.handle.send(Command::Stop);
+            let _ = self.handle.send(Command::Stop(None));
             match handle.join() {
                 Ok(()) => (),
                 Err(e) => error!("join error: {e:?}"),
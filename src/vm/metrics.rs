@@ -0,0 +1,258 @@
+use std::time::Duration;
+use anyhow::Result;
+#[cfg(feature = "metrics")]
+use std::collections::BTreeMap;
+#[cfg(feature = "metrics")]
+use std::fmt::Write;
+#[cfg(feature = "metrics")]
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+    #[cfg(feature = "metrics")]
+    inner: Option<Arc<Inner>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    Success,
+    Error,
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+pub struct Heap {
+    pub used:  usize,
+    pub total: usize,
+    pub limit: usize,
+}
+
+#[cfg(feature = "metrics")]
+pub trait Recorder: Send + Sync + 'static {
+    fn call(&self, script: &str, export: &str, outcome: Outcome, latency: Duration);
+    fn queue(&self, script: &str, wait: Duration);
+    fn depth(&self, script: &str, depth: usize);
+    fn settle(&self, script: &str, export: &str, time: Duration);
+    fn heap(&self, script: &str, heap: Heap);
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+pub struct Prometheus {
+    registry: Mutex<Registry>,
+}
+
+#[cfg(feature = "metrics")]
+struct Inner {
+    script:   String,
+    recorder: Arc<dyn Recorder>,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Registry {
+    calls:   BTreeMap<(String, String, Outcome), u64>,
+    latency: BTreeMap<(String, String), Histogram>,
+    queue:   BTreeMap<String, Histogram>,
+    depth:   BTreeMap<String, usize>,
+    settle:  BTreeMap<(String, String), Histogram>,
+    heap:    BTreeMap<String, Heap>,
+}
+
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count:   u64,
+    sum:     f64,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    pub fn new(script: &str, recorder: Arc<dyn Recorder>) -> Self {
+        let script = script.to_owned();
+        Self {
+            inner: Some(Arc::new(Inner { script, recorder })),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+impl Metrics {
+    pub fn call(&self, export: &str, outcome: Outcome, latency: Duration) {
+        #[cfg(feature = "metrics")]
+        if let Some(inner) = &self.inner {
+            inner.recorder.call(&inner.script, export, outcome, latency);
+        }
+    }
+
+    pub fn queue(&self, wait: Duration) {
+        #[cfg(feature = "metrics")]
+        if let Some(inner) = &self.inner {
+            inner.recorder.queue(&inner.script, wait);
+        }
+    }
+
+    pub fn depth(&self, depth: usize) {
+        #[cfg(feature = "metrics")]
+        if let Some(inner) = &self.inner {
+            inner.recorder.depth(&inner.script, depth);
+        }
+    }
+
+    pub fn settle(&self, export: &str, time: Duration) {
+        #[cfg(feature = "metrics")]
+        if let Some(inner) = &self.inner {
+            inner.recorder.settle(&inner.script, export, time);
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub fn heap(&self, heap: Heap) {
+        if let Some(inner) = &self.inner {
+            inner.recorder.heap(&inner.script, heap);
+        }
+    }
+}
+
+impl<T> From<&Result<T>> for Outcome {
+    fn from(result: &Result<T>) -> Self {
+        match result {
+            Ok(_)  => Self::Success,
+            Err(_) => Self::Error,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Success   => "success",
+            Self::Error     => "error",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Prometheus {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    pub fn render(&self) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out  = String::new();
+
+        writeln!(out, "# TYPE v8vm_calls_total counter").unwrap();
+        for ((script, export, outcome), count) in &registry.calls {
+            let labels = labels(&[("script", script), ("export", export), ("outcome", outcome.name())]);
+            writeln!(out, "v8vm_calls_total{{{labels}}} {count}").unwrap();
+        }
+
+        writeln!(out, "# TYPE v8vm_call_duration_seconds histogram").unwrap();
+        for ((script, export), histogram) in &registry.latency {
+            let labels = labels(&[("script", script), ("export", export)]);
+            histogram.render(&mut out, "v8vm_call_duration_seconds", &labels);
+        }
+
+        writeln!(out, "# TYPE v8vm_queue_wait_seconds histogram").unwrap();
+        for (script, histogram) in &registry.queue {
+            let labels = labels(&[("script", script)]);
+            histogram.render(&mut out, "v8vm_queue_wait_seconds", &labels);
+        }
+
+        writeln!(out, "# TYPE v8vm_queue_depth gauge").unwrap();
+        for (script, depth) in &registry.depth {
+            let labels = labels(&[("script", script)]);
+            writeln!(out, "v8vm_queue_depth{{{labels}}} {depth}").unwrap();
+        }
+
+        writeln!(out, "# TYPE v8vm_promise_settle_seconds histogram").unwrap();
+        for ((script, export), histogram) in &registry.settle {
+            let labels = labels(&[("script", script), ("export", export)]);
+            histogram.render(&mut out, "v8vm_promise_settle_seconds", &labels);
+        }
+
+        for (index, name) in HEAP.iter().enumerate() {
+            writeln!(out, "# TYPE {name} gauge").unwrap();
+            for (script, heap) in &registry.heap {
+                let labels = labels(&[("script", script)]);
+                let value  = [heap.used, heap.total, heap.limit][index];
+                writeln!(out, "{name}{{{labels}}} {value}").unwrap();
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Recorder for Prometheus {
+    fn call(&self, script: &str, export: &str, outcome: Outcome, latency: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        let key = (script.to_owned(), export.to_owned());
+        *registry.calls.entry((key.0.clone(), key.1.clone(), outcome)).or_default() += 1;
+        registry.latency.entry(key).or_default().observe(latency);
+    }
+
+    fn queue(&self, script: &str, wait: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.queue.entry(script.to_owned()).or_default().observe(wait);
+    }
+
+    fn depth(&self, script: &str, depth: usize) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.depth.insert(script.to_owned(), depth);
+    }
+
+    fn settle(&self, script: &str, export: &str, time: Duration) {
+        let mut registry = self.registry.lock().unwrap();
+        let key = (script.to_owned(), export.to_owned());
+        registry.settle.entry(key).or_default().observe(time);
+    }
+
+    fn heap(&self, script: &str, heap: Heap) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.heap.insert(script.to_owned(), heap);
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Histogram {
+    fn observe(&mut self, value: Duration) {
+        let value = value.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum   += value;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        for (count, bound) in self.buckets.iter().zip(BUCKETS) {
+            writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}").unwrap();
+        }
+        writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count).unwrap();
+        writeln!(out, "{name}_sum{{{labels}}} {}", self.sum).unwrap();
+        writeln!(out, "{name}_count{{{labels}}} {}", self.count).unwrap();
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn labels(pairs: &[(&str, &str)]) -> String {
+    pairs.iter().map(|(name, value)| {
+        let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        format!("{name}=\"{value}\"")
+    }).collect::<Vec<_>>().join(",")
+}
+
+#[cfg(feature = "metrics")]
+const BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+#[cfg(feature = "metrics")]
+const HEAP: [&str; 3] = ["v8vm_heap_used_bytes", "v8vm_heap_total_bytes", "v8vm_heap_limit_bytes"];
@@ -4,14 +4,56 @@ pub use machine::Guard;
 pub use machine::Handle;
 pub use machine::Machine;
 
+pub use coverage::Coverage;
+pub use coverage::CoverageRange;
+pub use coverage::FunctionCoverage;
+pub use coverage::LineCoverage;
+pub use coverage::Position;
+
+pub use exports::Entry;
+pub use exports::Kind;
+
+pub use failure::Failure;
+pub use failure::Frame;
+pub use failure::Location;
+
 pub use promise::Promise;
 pub use promise::Promises;
 pub use promise::Resolved;
 pub use promise::Resolver;
 
+pub use queue::Priority;
+
+pub use realm::Realm;
+
+pub use state::Status;
+
+#[cfg(feature = "metrics")]
+pub use metrics::{Heap, Outcome, Prometheus, Recorder};
+
+pub use supervise::Backoff;
+pub use supervise::Health;
+pub use supervise::Restarted;
+
+pub use trace::Trace;
+
 mod adjunct;
 mod channel;
 mod context;
+mod coverage;
+mod exports;
+mod failure;
 mod inspect;
 mod machine;
+mod metrics;
 mod promise;
+mod queue;
+mod realm;
+mod signal;
+mod srcmap;
+mod state;
+mod supervise;
+mod trace;
+
+#[cfg(feature = "typescript")]
+mod typescript;
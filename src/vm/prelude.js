@@ -0,0 +1,83 @@
+(() => {
+    if (typeof globalThis.AbortController === "function") {
+        return;
+    }
+
+    const abort = Symbol("abort");
+
+    class AbortSignal {
+        #listeners = [];
+
+        aborted = false;
+        reason  = undefined;
+        onabort = null;
+
+        static abort(reason) {
+            const controller = new AbortController();
+            controller.abort(reason);
+            return controller.signal;
+        }
+
+        addEventListener(type, listener) {
+            if (type === "abort" && typeof listener === "function") {
+                this.#listeners.push(listener);
+            }
+        }
+
+        removeEventListener(type, listener) {
+            if (type === "abort") {
+                this.#listeners = this.#listeners.filter(l => l !== listener);
+            }
+        }
+
+        throwIfAborted() {
+            if (this.aborted) {
+                throw this.reason;
+            }
+        }
+
+        [abort](reason) {
+            if (this.aborted) {
+                return;
+            }
+
+            this.aborted = true;
+            this.reason  = reason === undefined ? aborted() : reason;
+
+            const event     = { type: "abort", target: this };
+            const listeners = [this.onabort, ...this.#listeners];
+
+            for (const listener of listeners) {
+                try {
+                    if (typeof listener === "function") {
+                        listener.call(this, event);
+                    }
+                } catch (e) {
+                    console.error(e);
+                }
+            }
+        }
+    }
+
+    class AbortController {
+        signal = new AbortSignal();
+
+        abort(reason) {
+            this.signal[abort](reason);
+        }
+    }
+
+    function aborted() {
+        const error = new Error("This operation was aborted");
+        error.name  = "AbortError";
+        return error;
+    }
+
+    for (const value of [AbortSignal, AbortController]) {
+        Object.defineProperty(globalThis, value.name, {
+            value:        value,
+            writable:     true,
+            configurable: true,
+        });
+    }
+})();
@@ -4,7 +4,6 @@ use v8::{self, Global, HandleScope, Local, PromiseResolver, Value};
 use super::machine::Handle;
 
 pub struct Promises {
-    counter: u64,
     handle:  Handle,
     pending: HashMap<u64, Global<PromiseResolver>>,
 }
@@ -15,8 +14,9 @@ pub enum Promise {
 }
 
 pub struct Resolver {
-    id: u64,
-    tx: Handle,
+    id:      u64,
+    tx:      Handle,
+    settled: bool,
 }
 
 pub trait Resolved: Send + 'static {
@@ -26,7 +26,6 @@ pub trait Resolved: Send + 'static {
 impl Promises {
     pub fn new(handle: Handle) -> Self {
         Self {
-            counter: 0,
             handle:  handle,
             pending: HashMap::new(),
         }
@@ -35,13 +34,13 @@ impl Promises {
     pub fn insert(local: Local<v8::Value>, resolver: Global<PromiseResolver>) -> Result<Resolver> {
         let promises = Self::get(local)?;
 
-        let id = promises.counter;
+        let id = promises.handle.next();
         let tx = promises.handle.clone();
 
-        promises.counter += 1;
         promises.pending.insert(id, resolver);
+        promises.handle.track(promises.pending.len());
 
-        Ok(Resolver { id, tx })
+        Ok(Resolver { id, tx, settled: false })
     }
 
     pub fn settle(local: Local<Value>, scope: &mut HandleScope, promise: Promise) -> Result<()> {
@@ -50,18 +49,33 @@ impl Promises {
             Failure(Local<'s, v8::Value>),
         }
 
-        let (id, value) = match promise {
-            Promise::Success(id, v) => (id, Value::Success(v.value(scope)?)),
-            Promise::Failure(id, v) => (id, Value::Failure(v.value(scope)?)),
+        let id = match &promise {
+            Promise::Success(id, _) => *id,
+            Promise::Failure(id, _) => *id,
         };
 
-        if let Some(resolver) = Self::get(local)?.pending.remove(&id) {
-            let resolver = Local::new(scope, resolver);
-            match value {
-                Value::Success(v) => resolver.resolve(scope, v),
-                Value::Failure(v) => resolver.reject(scope, v),
-            };
-        }
+        let promises = Self::get(local)?;
+        let resolver = match promises.pending.remove(&id) {
+            Some(resolver) => resolver,
+            None           => return Ok(()),
+        };
+        promises.handle.track(promises.pending.len());
+
+        // build the value in the realm that created the promise
+        let resolver = Local::new(scope, resolver);
+        let context  = resolver.get_promise(scope).get_creation_context(scope);
+        let context  = context.unwrap_or_else(|| scope.get_current_context());
+        let scope    = &mut v8::ContextScope::new(scope, context);
+
+        let value = match promise {
+            Promise::Success(_, v) => Value::Success(v.value(scope)?),
+            Promise::Failure(_, v) => Value::Failure(v.value(scope)?),
+        };
+
+        match value {
+            Value::Success(v) => resolver.resolve(scope, v),
+            Value::Failure(v) => resolver.reject(scope, v),
+        };
 
         Ok(())
     }
@@ -74,17 +88,28 @@ impl Promises {
 }
 
 impl Resolver {
-    pub fn resolve(self, value: Box<dyn Resolved>) -> Result<()> {
-        match self.tx.done(Promise::Success(self.id, value)) {
+    pub fn resolve(mut self, value: Box<dyn Resolved>) -> Result<()> {
+        self.settle(Promise::Success(self.id, value))
+    }
+
+    pub fn reject(mut self, value: Box<dyn Resolved>) -> Result<()> {
+        self.settle(Promise::Failure(self.id, value))
+    }
+
+    fn settle(&mut self, promise: Promise) -> Result<()> {
+        self.settled = true;
+        match self.tx.done(promise) {
             Ok(()) => Ok(()),
             Err(_) => Err(anyhow!("channel closed")),
         }
     }
+}
 
-    pub fn reject(self, value: Box<dyn Resolved>) -> Result<()> {
-        match self.tx.done(Promise::Failure(self.id, value)) {
-            Ok(()) => Ok(()),
-            Err(_) => Err(anyhow!("channel closed")),
+impl Drop for Resolver {
+    fn drop(&mut self) {
+        if !self.settled {
+            let error = Box::new(anyhow!("resolver dropped"));
+            let _ = self.settle(Promise::Failure(self.id, error));
         }
     }
 }
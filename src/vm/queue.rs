@@ -0,0 +1,205 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::task::{Context, Poll, Waker};
+use anyhow::{anyhow, Result};
+use super::context::Call;
+use super::metrics::Metrics;
+
+pub struct Queue {
+    inner:   Mutex<Inner>,
+    metrics: Metrics,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+struct Inner {
+    levels:   [Level; 3],
+    weights:  HashMap<String, u32>,
+    depth:    usize,
+    capacity: Option<usize>,
+    waiters:  Vec<Waker>,
+    closed:   bool,
+}
+
+#[derive(Default)]
+struct Level {
+    tenants: VecDeque<Tenant>,
+}
+
+struct Tenant {
+    key:    String,
+    calls:  VecDeque<Call>,
+    weight: u32,
+    credit: u32,
+}
+
+impl Queue {
+    pub fn new(capacity: Option<usize>, weights: HashMap<String, u32>, metrics: Metrics) -> Self {
+        Self {
+            metrics: metrics,
+            inner:   Mutex::new(Inner {
+                levels:   Default::default(),
+                weights:  weights,
+                depth:    0,
+                capacity: capacity,
+                waiters:  Vec::new(),
+                closed:   false,
+            }),
+        }
+    }
+
+    pub fn push(&self, call: Call, priority: Priority, tenant: &str) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.status() {
+            Status::Open   => inner.insert(call, priority, tenant),
+            Status::Full   => return Err(anyhow!("queue full")),
+            Status::Closed => return Err(anyhow!("machine terminated")),
+        }
+        self.metrics.depth(inner.depth);
+        Ok(())
+    }
+
+    pub fn poll_push(
+        &self,
+        call:     &mut Option<Call>,
+        priority: Priority,
+        tenant:   &str,
+        cx:       &mut Context<'_>,
+    ) -> Poll<Result<()>> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.status() {
+            Status::Open   => {
+                if let Some(call) = call.take() {
+                    inner.insert(call, priority, tenant);
+                }
+            },
+            Status::Full   => {
+                inner.waiters.push(cx.waker().clone());
+                return Poll::Pending;
+            },
+            Status::Closed => return Poll::Ready(Err(anyhow!("machine terminated"))),
+        }
+        self.metrics.depth(inner.depth);
+        Poll::Ready(Ok(()))
+    }
+
+    pub fn pop(&self) -> Option<Call> {
+        let mut inner = self.inner.lock().unwrap();
+        let call = inner.levels.iter_mut().find_map(Level::pop);
+        if call.is_some() {
+            inner.depth -= 1;
+            inner.wake();
+            self.metrics.depth(inner.depth);
+        }
+        call
+    }
+
+    pub fn remove(&self, id: u64) -> Option<Call> {
+        let mut inner = self.inner.lock().unwrap();
+        let call = inner.levels.iter_mut().find_map(|level| level.remove(id));
+        if call.is_some() {
+            inner.depth -= 1;
+            inner.wake();
+            self.metrics.depth(inner.depth);
+        }
+        call
+    }
+
+    pub fn depth(&self) -> usize {
+        self.inner.lock().unwrap().depth
+    }
+
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.closed = true;
+        inner.depth  = 0;
+        inner.wake();
+        self.metrics.depth(0);
+
+        for level in &mut inner.levels {
+            for tenant in level.tenants.drain(..) {
+                for call in tenant.calls {
+                    call.sender.send(Err(anyhow!("machine stopped")));
+                }
+            }
+        }
+    }
+}
+
+enum Status {
+    Open,
+    Full,
+    Closed,
+}
+
+impl Inner {
+    fn status(&self) -> Status {
+        match self.capacity {
+            _ if self.closed           => Status::Closed,
+            Some(n) if self.depth >= n => Status::Full,
+            _                          => Status::Open,
+        }
+    }
+
+    fn insert(&mut self, call: Call, priority: Priority, tenant: &str) {
+        let weight = self.weights.get(tenant).copied().unwrap_or(1).max(1);
+        let level  = &mut self.levels[priority as usize];
+
+        match level.tenants.iter_mut().find(|t| t.key == tenant) {
+            Some(tenant) => tenant.calls.push_back(call),
+            None         => level.tenants.push_back(Tenant {
+                key:    tenant.to_owned(),
+                calls:  VecDeque::from([call]),
+                weight: weight,
+                credit: weight,
+            }),
+        }
+
+        self.depth += 1;
+    }
+
+    fn wake(&mut self) {
+        for waker in self.waiters.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl Level {
+    fn pop(&mut self) -> Option<Call> {
+        let tenant = self.tenants.front_mut()?;
+        let call   = tenant.calls.pop_front();
+        tenant.credit -= 1;
+
+        if tenant.calls.is_empty() {
+            self.tenants.pop_front();
+        } else if tenant.credit == 0 {
+            tenant.credit = tenant.weight;
+            self.tenants.rotate_left(1);
+        }
+
+        call
+    }
+
+    fn remove(&mut self, id: u64) -> Option<Call> {
+        let (index, position) = self.tenants.iter().enumerate().find_map(|(index, tenant)| {
+            let position = tenant.calls.iter().position(|call| call.id == id)?;
+            Some((index, position))
+        })?;
+
+        let tenant = &mut self.tenants[index];
+        let call   = tenant.calls.remove(position);
+
+        if tenant.calls.is_empty() {
+            self.tenants.remove(index);
+        }
+
+        call
+    }
+}
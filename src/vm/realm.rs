@@ -0,0 +1,25 @@
+use super::adjunct::Adjunct;
+
+pub struct Realm {
+    pub(super) module: String,
+    pub(super) srcmap: Option<String>,
+    pub(super) extra:  Vec<Box<dyn Adjunct>>,
+}
+
+impl Realm {
+    pub fn new(module: String) -> Self {
+        Self {
+            module: module,
+            srcmap: None,
+            extra:  Vec::new(),
+        }
+    }
+
+    pub fn source_map(&mut self, srcmap: String) {
+        self.srcmap = Some(srcmap);
+    }
+
+    pub fn extend<T: Adjunct>(&mut self, adjunct: Box<T>) {
+        self.extra.push(adjunct);
+    }
+}
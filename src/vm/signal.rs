@@ -0,0 +1,30 @@
+use v8::{self, Global, HandleScope, Local};
+
+// abort signal of the call whose code is running
+pub struct Signal(pub Global<v8::Value>);
+
+pub fn install(scope: &mut HandleScope) {
+    let context = scope.get_current_context();
+    let global  = context.global(scope);
+    let object  = v8::Object::new(scope);
+
+    let name = v8::String::new(scope, "current").unwrap();
+    let func = v8::Function::new(scope, current).unwrap();
+    object.set(scope, name.into(), func.into());
+
+    let name = v8::String::new(scope, "signal").unwrap();
+    global.set(scope, name.into(), object.into());
+}
+
+fn current(
+  scope:      &mut v8::HandleScope,
+  _args:      v8::FunctionCallbackArguments,
+  mut result: v8::ReturnValue,
+) {
+    let signal = match scope.get_slot::<Signal>() {
+        Some(Signal(signal)) => signal.clone(),
+        None                 => return result.set_null(),
+    };
+
+    result.set(Local::new(scope, signal));
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+#[derive(Default)]
+pub struct SourceMaps {
+    maps: HashMap<String, SourceMap>,
+}
+
+pub struct SourceMap {
+    sources:  Vec<String>,
+    contents: Vec<Option<String>>,
+    names:    Vec<String>,
+    lines:    Vec<Vec<Segment>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    pub source:  String,
+    pub line:    usize,
+    pub column:  usize,
+    pub name:    Option<String>,
+    pub content: Option<String>,
+}
+
+struct Segment {
+    column: usize,
+    source: Option<Origin>,
+}
+
+struct Origin {
+    source: usize,
+    line:   usize,
+    column: usize,
+    name:   Option<usize>,
+}
+
+impl SourceMaps {
+    pub fn insert(&mut self, script: &str, map: SourceMap) {
+        self.maps.insert(script.to_owned(), map);
+    }
+
+    pub fn remove(&mut self, script: &str) {
+        self.maps.remove(script);
+    }
+
+    pub fn lookup(&self, script: &str, line: usize, column: usize) -> Option<Mapping> {
+        self.maps.get(script)?.lookup(line, column)
+    }
+}
+
+impl SourceMap {
+    pub fn parse(json: &str) -> Result<Self> {
+        let map = serde_json::from_str::<Value>(json)?;
+
+        if map.get("sections").is_some() {
+            return Err(anyhow!("indexed source maps are not supported"));
+        }
+
+        let root = map["sourceRoot"].as_str().unwrap_or_default();
+        let root = root.trim_end_matches('/');
+
+        let sources = strings(&map["sources"]).into_iter().map(|source| {
+            let source = source.unwrap_or_default();
+            match root {
+                ""   => source,
+                root => format!("{root}/{source}"),
+            }
+        }).collect::<Vec<_>>();
+
+        let contents = strings(&map["sourcesContent"]);
+        let names    = strings(&map["names"]).into_iter().map(Option::unwrap_or_default).collect();
+        let mappings = map["mappings"].as_str().unwrap_or_default();
+
+        Ok(Self {
+            lines:    decode(mappings, sources.len())?,
+            sources:  sources,
+            contents: contents,
+            names:    names,
+        })
+    }
+
+    pub fn inline(code: &str) -> Option<Result<Self>> {
+        let url = code.lines().rev().find_map(|line| {
+            let line = line.trim();
+            line.strip_prefix("//# sourceMappingURL=").or_else(|| {
+                line.strip_prefix("//@ sourceMappingURL=")
+            })
+        })?;
+
+        let data = url.strip_prefix("data:application/json")?;
+        let (_, data) = data.split_once(";base64,")?;
+
+        Some(base64(data).and_then(|json| {
+            Self::parse(&String::from_utf8(json)?)
+        }))
+    }
+
+    pub fn lookup(&self, line: usize, column: usize) -> Option<Mapping> {
+        let segments = self.lines.get(line)?;
+        let index    = segments.partition_point(|s| s.column <= column);
+        let origin   = segments[..index].last()?.source.as_ref()?;
+
+        let content = self.contents.get(origin.source).and_then(|content| {
+            content.as_ref()?.lines().nth(origin.line).map(str::to_owned)
+        });
+
+        Some(Mapping {
+            source:  self.sources[origin.source].clone(),
+            line:    origin.line,
+            column:  origin.column,
+            name:    origin.name.and_then(|n| self.names.get(n).cloned()),
+            content: content,
+        })
+    }
+}
+
+fn strings(value: &Value) -> Vec<Option<String>> {
+    value.as_array().into_iter().flatten().map(|value| {
+        value.as_str().map(str::to_owned)
+    }).collect()
+}
+
+fn decode(mappings: &str, sources: usize) -> Result<Vec<Vec<Segment>>> {
+    let mut source = 0;
+    let mut line   = 0;
+    let mut column = 0;
+    let mut name   = 0;
+
+    mappings.split(';').map(|segments| {
+        let mut generated = 0;
+
+        let mut segments = segments.split(',').filter(|s| !s.is_empty()).map(|segment| {
+            let fields = vlq(segment)?;
+
+            generated = offset(generated, fields[0])?;
+
+            let origin = match fields.len() {
+                1 => None,
+                4 | 5 => {
+                    source = offset(source, fields[1])?;
+                    line   = offset(line,   fields[2])?;
+                    column = offset(column, fields[3])?;
+
+                    if source >= sources {
+                        return Err(anyhow!("invalid source index {source}"));
+                    }
+
+                    let index = match fields.get(4) {
+                        Some(delta) => { name = offset(name, *delta)?; Some(name) },
+                        None        => None,
+                    };
+
+                    Some(Origin { source, line, column, name: index })
+                },
+                n => return Err(anyhow!("invalid segment length {n}")),
+            };
+
+            Ok(Segment { column: generated, source: origin })
+        }).collect::<Result<Vec<_>>>()?;
+
+        segments.sort_by_key(|s| s.column);
+
+        Ok(segments)
+    }).collect()
+}
+
+fn vlq(segment: &str) -> Result<Vec<i64>> {
+    let mut fields = Vec::new();
+    let mut value  = 0i64;
+    let mut shift  = 0;
+
+    for c in segment.bytes() {
+        let digit = digit(c)? as i64;
+
+        value |= (digit & 0b11111) << shift;
+        shift += 5;
+
+        if digit & 0b100000 == 0 {
+            let negative = value & 1 == 1;
+            value >>= 1;
+            fields.push(if negative { -value } else { value });
+            value = 0;
+            shift = 0;
+        } else if shift > 60 {
+            return Err(anyhow!("invalid VLQ in {segment}"));
+        }
+    }
+
+    match shift {
+        0 => Ok(fields),
+        _ => Err(anyhow!("truncated VLQ in {segment}")),
+    }
+}
+
+fn base64(data: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut value = 0u32;
+    let mut bits  = 0;
+
+    for c in data.bytes().filter(|c| !c.is_ascii_whitespace() && *c != b'=') {
+        value = (value << 6 | digit(c)?) & 0xffff;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((value >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+fn digit(c: u8) -> Result<u32> {
+    match c {
+        b'A'..=b'Z' => Ok((c - b'A') as u32),
+        b'a'..=b'z' => Ok((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Ok((c - b'0') as u32 + 52),
+        b'+'        => Ok(62),
+        b'/'        => Ok(63),
+        _           => Err(anyhow!("invalid base64 character {:?}", c as char)),
+    }
+}
+
+fn offset(base: usize, delta: i64) -> Result<usize> {
+    match usize::try_from(base as i64 + delta) {
+        Ok(value) => Ok(value),
+        Err(_)    => Err(anyhow!("negative source map offset")),
+    }
+}
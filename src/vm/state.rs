@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use v8::IsolateHandle;
+use super::metrics::Metrics;
+use super::queue::Queue;
+use super::supervise::Health;
+
+pub struct State {
+    pub queue:   Queue,
+    pub metrics: Metrics,
+    health:    Mutex<Health>,
+    started:   Mutex<Option<Instant>>,
+    restarts:  AtomicUsize,
+    served:    AtomicU64,
+    latency:   Mutex<Option<Duration>>,
+    promises:  AtomicUsize,
+    in_flight: AtomicUsize,
+    calls:     AtomicU64,
+    compiles:  AtomicU64,
+    exhausted: AtomicBool,
+    running:   Mutex<Running>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Status {
+    pub health:    Health,
+    pub uptime:    Duration,
+    pub served:    u64,
+    pub latency:   Option<Duration>,
+    pub queued:    usize,
+    pub in_flight: usize,
+    pub promises:  usize,
+    pub restarts:  usize,
+    pub compiles:  u64,
+}
+
+#[derive(Default)]
+struct Running {
+    call:       Option<u64>,
+    isolate:    Option<IsolateHandle>,
+    terminated: bool,
+    cancelled:  HashSet<u64>,
+}
+
+impl State {
+    pub fn new(capacity: Option<usize>, weights: HashMap<String, u32>, metrics: Metrics) -> Self {
+        Self {
+            queue:     Queue::new(capacity, weights, metrics.clone()),
+            metrics:   metrics,
+            health:    Mutex::new(Health::Starting),
+            started:   Mutex::new(None),
+            restarts:  AtomicUsize::new(0),
+            served:    AtomicU64::new(0),
+            latency:   Mutex::new(None),
+            promises:  AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            calls:     AtomicU64::new(0),
+            compiles:  AtomicU64::new(0),
+            exhausted: AtomicBool::new(false),
+            running:   Mutex::default(),
+        }
+    }
+
+    pub fn health(&self) -> Health {
+        *self.health.lock().unwrap()
+    }
+
+    pub fn set_health(&self, health: Health) {
+        *self.health.lock().unwrap()  = health;
+        *self.started.lock().unwrap() = match health {
+            Health::Running => Some(Instant::now()),
+            _               => None,
+        };
+    }
+
+    pub fn status(&self) -> Status {
+        let started = *self.started.lock().unwrap();
+        Status {
+            health:    self.health(),
+            uptime:    started.map(|t| t.elapsed()).unwrap_or_default(),
+            served:    self.served.load(Ordering::Relaxed),
+            latency:   *self.latency.lock().unwrap(),
+            queued:    self.queue.depth(),
+            in_flight: self.in_flight(),
+            promises:  self.promises(),
+            restarts:  self.restarts(),
+            compiles:  self.compiles.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn served(&self, latency: Duration) {
+        self.served.fetch_add(1, Ordering::Relaxed);
+        *self.latency.lock().unwrap() = Some(latency);
+    }
+
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    pub fn restarted(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn promises(&self) -> usize {
+        self.promises.load(Ordering::Relaxed)
+    }
+
+    pub fn track(&self, promises: usize) {
+        self.promises.store(promises, Ordering::Relaxed);
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub fn set_in_flight(&self, in_flight: usize) {
+        self.in_flight.store(in_flight, Ordering::Relaxed);
+    }
+
+    pub fn set_compiles(&self, compiles: u64) {
+        self.compiles.store(compiles, Ordering::Relaxed);
+    }
+
+    pub fn next(&self) -> u64 {
+        self.calls.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub fn attach(&self, isolate: IsolateHandle) {
+        self.exhausted.store(false, Ordering::Relaxed);
+        self.running.lock().unwrap().isolate = Some(isolate);
+    }
+
+    pub fn exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+
+    // the isolate stays terminated, the machine fails or restarts
+    pub fn exhaust(&self) {
+        self.exhausted.store(true, Ordering::Relaxed);
+        if let Some(isolate) = &self.running.lock().unwrap().isolate {
+            isolate.terminate_execution();
+        }
+    }
+
+    pub fn start(&self, call: u64) -> bool {
+        let mut running = self.running.lock().unwrap();
+        if running.cancelled.contains(&call) {
+            return false;
+        }
+        running.call = Some(call);
+        true
+    }
+
+    pub fn finish(&self) {
+        let mut running = self.running.lock().unwrap();
+        running.call = None;
+
+        if running.terminated {
+            running.terminated = false;
+            if let Some(isolate) = &running.isolate {
+                isolate.cancel_terminate_execution();
+            }
+        }
+    }
+
+    pub fn cancel(&self, call: u64) {
+        let mut running = self.running.lock().unwrap();
+        running.cancelled.insert(call);
+
+        if running.call == Some(call) {
+            if let Some(isolate) = &running.isolate {
+                running.terminated = isolate.terminate_execution();
+            }
+        }
+    }
+
+    pub fn forget(&self, call: u64) {
+        self.running.lock().unwrap().cancelled.remove(&call);
+    }
+}
@@ -0,0 +1,46 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max:     Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    Starting,
+    Running,
+    Restarting,
+    Stopped,
+    Failed,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Restarted;
+
+impl Backoff {
+    pub fn next(&self, delay: Duration, uptime: Duration) -> Duration {
+        match uptime >= self.max {
+            true  => self.initial,
+            false => (delay * 2).clamp(self.initial, self.max),
+        }
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max:     Duration::from_secs(30),
+        }
+    }
+}
+
+impl Display for Restarted {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "machine restarted")
+    }
+}
+
+impl std::error::Error for Restarted {}
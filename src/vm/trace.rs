@@ -0,0 +1,102 @@
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Display, Formatter};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde_json::json;
+use v8::{self, HandleScope, Isolate};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Trace {
+    pub id:   u128,
+    pub span: u64,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        let id = (random() as u128) << 64 | random() as u128;
+        Self { id, span: random() }
+    }
+
+    pub fn child(&self) -> Self {
+        Self { id: self.id, span: random() }
+    }
+
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+
+        let version = parts.next()?;
+        let id      = parts.next()?;
+        let span    = parts.next()?;
+        let flags   = parts.next()?;
+
+        if version.len() != 2 || id.len() != 32 || span.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+
+        let id   = u128::from_str_radix(id, 16).ok()?;
+        let span = u64::from_str_radix(span, 16).ok()?;
+
+        match (id, span) {
+            (0, _) | (_, 0) => None,
+            _               => Some(Self { id, span }),
+        }
+    }
+
+    pub fn current(isolate: &Isolate) -> Option<Self> {
+        isolate.get_slot::<Self>().copied()
+    }
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "00-{:032x}-{:016x}-01", self.id, self.span)
+    }
+}
+
+pub fn install(scope: &mut HandleScope) {
+    let context = scope.get_current_context();
+    let global  = context.global(scope);
+    let object  = v8::Object::new(scope);
+
+    let name = v8::String::new(scope, "current").unwrap();
+    let func = v8::Function::new(scope, current).unwrap();
+    object.set(scope, name.into(), func.into());
+
+    let name = v8::String::new(scope, "trace").unwrap();
+    global.set(scope, name.into(), object.into());
+}
+
+fn current(
+  scope:      &mut v8::HandleScope,
+  _args:      v8::FunctionCallbackArguments,
+  mut result: v8::ReturnValue,
+) {
+    let trace = match Trace::current(scope) {
+        Some(trace) => trace,
+        None        => return result.set_null(),
+    };
+
+    let value = json!({
+        "traceId":     format!("{:032x}", trace.id),
+        "spanId":      format!("{:016x}", trace.span),
+        "traceparent": trace.to_string(),
+    });
+
+    if let Ok(value) = serde_v8::to_v8(scope, value) {
+        result.set(value);
+    }
+}
+
+fn random() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish().max(1)
+}
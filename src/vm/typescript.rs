@@ -0,0 +1,36 @@
+use anyhow::Result;
+use deno_ast::{parse_module, EmitOptions, MediaType, ParseParams, SourceTextInfo};
+
+pub fn transpile(
+    name:   &str,
+    module: String,
+    srcmap: Option<String>,
+) -> Result<(String, Option<String>)> {
+    let media = MediaType::from(&name.to_owned());
+
+    match media {
+        MediaType::TypeScript => (),
+        MediaType::Mts        => (),
+        MediaType::Cts        => (),
+        MediaType::Tsx        => (),
+        _                     => return Ok((module, srcmap)),
+    }
+
+    let parsed = parse_module(ParseParams {
+        specifier:      name.to_owned(),
+        text_info:      SourceTextInfo::from_string(module),
+        media_type:     media,
+        capture_tokens: false,
+        scope_analysis: false,
+        maybe_syntax:   None,
+    })?;
+
+    let source = parsed.transpile(&EmitOptions {
+        inline_source_map: false,
+        inline_sources:    true,
+        source_map:        true,
+        ..Default::default()
+    })?;
+
+    Ok((source.text, source.source_map))
+}
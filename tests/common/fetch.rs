@@ -1,6 +1,7 @@
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use anyhow::{anyhow, Result};
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use tokio::runtime::Handle;
 use tokio::time::timeout;
 use v8vm::vm::Resolver;
@@ -11,6 +12,10 @@ pub struct HttpClient {
     handle: Handle,
 }
 
+pub struct Capture {
+    traces: Arc<Mutex<Vec<Option<String>>>>,
+}
+
 impl HttpClient {
     pub fn new(handle: Handle) -> Self {
         let client = Client::new();
@@ -18,10 +23,13 @@ impl HttpClient {
     }
 
     async fn send(client: Client, request: Request) -> Result<Response> {
-        let method = request.method.parse()?;
-        let url    = request.url.parse()?;
+        let method  = request.method.parse()?;
+        let url     = request.url.parse()?;
+        let headers = request.headers;
+
+        let mut request = reqwest::Request::new(method, url);
+        *request.headers_mut() = headers;
 
-        let request  = reqwest::Request::new(method, url);
         let response = client.execute(request).await?;
         let status   = response.status();
         let body     = response.text().await?;
@@ -49,3 +57,23 @@ impl fetch::Client for HttpClient {
         });
     }
 }
+
+impl Capture {
+    pub fn new(traces: Arc<Mutex<Vec<Option<String>>>>) -> Self {
+        Self { traces }
+    }
+}
+
+impl fetch::Client for Capture {
+    fn fetch(&self, request: Request, resolver: Resolver) {
+        let trace = request.headers.get("traceparent").and_then(|value| {
+            value.to_str().ok().map(String::from)
+        });
+        self.traces.lock().unwrap().push(trace);
+
+        let _ = resolver.resolve(Box::new(Response {
+            status: StatusCode::OK,
+            body:   String::new(),
+        }));
+    }
+}
@@ -0,0 +1,33 @@
+use v8::{self, HandleScope, ObjectTemplate};
+use v8vm::vm::{Adjunct, Promises};
+
+pub struct Forget;
+
+impl Adjunct for Forget {
+    fn install(&self, scope: &mut HandleScope<()>, global: &ObjectTemplate) {
+        let name  = v8::String::new(scope, "forget").unwrap();
+        let value = v8::FunctionTemplate::new(scope, forget);
+        global.set(name.into(), value.into());
+    }
+}
+
+fn forget(
+  scope:      &mut v8::HandleScope,
+  args:       v8::FunctionCallbackArguments,
+  mut result: v8::ReturnValue,
+) {
+    let scope = &mut v8::HandleScope::new(scope);
+
+    let global   = args.this();
+    let promises = global.get_internal_field(scope, 0).unwrap();
+
+    let resolver = v8::PromiseResolver::new(scope).unwrap();
+    let promise  = resolver.get_promise(scope);
+
+    let resolver = v8::Global::new(scope, resolver);
+    let resolver = Promises::insert(promises, resolver).unwrap();
+
+    std::thread::spawn(move || drop(resolver));
+
+    result.set(promise.into());
+}
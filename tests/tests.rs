@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::path::Path;
-use std::fs::read_to_string;
+use std::fs::{read_to_string, remove_file};
+use std::sync::{Arc, Mutex, Once};
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use serde::Deserialize;
 use serde_json::Value;
@@ -10,6 +12,7 @@ use tracing_subscriber::{fmt, registry};
 use tracing_subscriber::filter::{EnvFilter, LevelFilter};
 use v8::{new_default_platform, V8};
 use v8vm::{Machine, ex::Fetch};
+use v8vm::vm::{Backoff, Entry, Failure, Health, Kind, Priority, Realm, Restarted, Trace};
 mod common;
 
 #[derive(Debug, Deserialize)]
@@ -43,16 +46,50 @@ fn execute(Test { module, invoke, .. }: &Test) -> Result<Value> {
     function.call(invoke.args.clone())?.recv()
 }
 
+fn execute_async(Test { module, invoke, .. }: &Test) -> Result<Value> {
+    let runtime = Runtime::new()?;
+    let handle  = runtime.handle().clone();
+
+    let client = common::fetch::HttpClient::new(handle);
+    let fetch  = Fetch::new(client);
+
+    let mut machine = Machine::new(module.clone());
+    machine.extend(fetch);
+
+    runtime.block_on(async {
+        let (handle, guard) = machine.exec();
+        let function = handle.find_async(&invoke.name).await?;
+        let result   = function.call_async(invoke.args.clone()).await;
+        guard.shutdown().await;
+        result
+    })
+}
+
+fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let mut filter = EnvFilter::from_default_env();
+        filter = filter.add_directive(LevelFilter::WARN.into());
+        let print = fmt::layer().compact();
+        registry().with(filter).with(print).init();
+
+        let platform = new_default_platform(0, false).make_shared();
+        V8::initialize_platform(platform);
+        V8::initialize();
+    });
+}
+
+fn until<F: Fn() -> bool>(check: F) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !check() {
+        assert!(Instant::now() < deadline, "condition not reached");
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
 #[test]
 fn test() -> Result<()> {
-    let mut filter = EnvFilter::from_default_env();
-    filter = filter.add_directive(LevelFilter::WARN.into());
-    let print = fmt::layer().compact();
-    registry().with(filter).with(print).init();
-
-    let platform = new_default_platform(0, false).make_shared();
-    V8::initialize_platform(platform);
-    V8::initialize();
+    init();
 
     let path = Path::new(env!("CARGO_MANIFEST_DIR"));
     let file = path.join("tests/tests.yml");
@@ -70,6 +107,1045 @@ fn test() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_async() -> Result<()> {
+    init();
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let file = path.join("tests/tests.yml");
+    let data = read_to_string(file)?;
+
+    let tests = serde_yaml::from_str::<HashMap<String, Test>>(&data)?;
+
+    for (name, test) in tests {
+        println!("  test: {name}");
+        let result = execute_async(&test);
+        let result = result.map_err(|e| format!("{e:?}"));
+        assert_eq!(result, test.expect);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn heap() -> Result<()> {
+    init();
+
+    let machine = Machine::new("export let data = new Array(1024).fill(0)".to_owned());
+    let (handle, _guard) = machine.exec();
+
+    let stats = handle.heap_stats()?;
+    assert!(stats.used_heap_size() > 0);
+    assert!(stats.used_heap_size() <= stats.total_heap_size());
+    assert!(stats.total_heap_size() <= stats.heap_size_limit());
+
+    let file = std::env::temp_dir().join("v8vm-test.heapsnapshot");
+    handle.heap_snapshot(&file)?;
+
+    let data = read_to_string(&file)?;
+    remove_file(&file)?;
+    assert!(serde_json::from_str::<Value>(&data)?.get("snapshot").is_some());
+
+    Ok(())
+}
+
+#[test]
+fn coverage() -> Result<()> {
+    init();
+
+    let module = r#"
+export default function test(a) {
+    if (a) {
+        return 1;
+    }
+    return 2;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.coverage(true);
+
+    let (handle, _guard) = machine.exec();
+    let function = handle.find("default")?;
+    function.call(Value::from(false))?.recv()?;
+    function.call(Value::from(false))?.recv()?;
+
+    let coverage = handle.coverage()?;
+    let test = coverage.functions.iter().find(|f| f.name == "test").unwrap();
+    assert_eq!(test.count(), 2);
+
+    let lcov = coverage.lcov();
+    assert!(lcov.contains("FNDA:2,test\n"));
+    assert!(lcov.contains("DA:4,0\n"));
+    assert!(lcov.contains("DA:6,2\n"));
+
+    Ok(())
+}
+
+#[test]
+fn source_map() -> Result<()> {
+    init();
+
+    let module = r#"export default function test() {
+    throw new Error("failure");
+}
+//# sourceMappingURL=data:application/json;base64,eyJ2ZXJzaW9uIjozLCJzb3VyY2VzIjpbInNyYy90ZXN0LnRzIl0sIm5hbWVzIjpbXSwibWFwcGluZ3MiOiI7SUFTUSJ9
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, _guard) = machine.exec();
+    let function = handle.find("default")?;
+
+    let error   = function.call(())?.recv().unwrap_err();
+    let failure = error.downcast_ref::<Failure>().unwrap();
+    let frame   = &failure.stack[0];
+
+    assert_eq!(failure.message, "Error: failure");
+    assert_eq!(frame.script, "src/test.ts");
+    assert_eq!((frame.line, frame.column), (10, 9));
+
+    Ok(())
+}
+
+#[test]
+fn name() -> Result<()> {
+    init();
+
+    let module = r#"
+export default function test() {
+    throw new Error("failure");
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.name("file:///test.js".to_owned());
+
+    let (handle, _guard) = machine.exec();
+    let function = handle.find("default")?;
+
+    let error   = function.call(())?.recv().unwrap_err();
+    let failure = error.downcast_ref::<Failure>().unwrap();
+
+    assert_eq!(failure.stack[0].script, "file:///test.js");
+    assert_eq!(failure.stack[0].function.as_deref(), Some("test"));
+
+    Ok(())
+}
+
+#[cfg(feature = "typescript")]
+#[test]
+fn typescript() -> Result<()> {
+    init();
+
+    let module = r#"interface Args {
+    a: number;
+}
+
+export default function test({ a }: Args): number {
+    if (a < 0) throw new Error("negative");
+    return a * 2;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.name("file:///test.ts".to_owned());
+
+    let (handle, _guard) = machine.exec();
+    let function = handle.find("default")?;
+
+    let result = function.call(serde_json::json!({ "a": 21 }))?.recv()?;
+    assert_eq!(result, Value::from(42));
+
+    let error   = function.call(serde_json::json!({ "a": -1 }))?.recv().unwrap_err();
+    let failure = error.downcast_ref::<Failure>().unwrap();
+
+    assert_eq!(failure.stack[0].script, "file:///test.ts");
+    assert_eq!(failure.stack[0].line, 6);
+
+    Ok(())
+}
+
+#[test]
+fn resolver_dropped() -> Result<()> {
+    init();
+
+    let module = r#"
+export default async function test() {
+    try {
+        await forget();
+    } catch (e) {
+        return String(e);
+    }
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.extend(Box::new(common::forget::Forget));
+
+    let (handle, _guard) = machine.exec();
+    let function = handle.find("default")?;
+
+    let result = function.call(())?.recv()?;
+    assert_eq!(result, Value::from("Error: resolver dropped"));
+    assert_eq!(handle.pending_promises(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn pending_call() -> Result<()> {
+    init();
+
+    let module = r#"
+export default function test() {
+    return new Promise(() => {});
+}
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, guard) = machine.exec();
+    let function = handle.find("default")?;
+
+    let rx = function.call(())?;
+    drop(guard);
+
+    let error = rx.recv().unwrap_err();
+    assert_eq!(error.to_string(), "machine stopped");
+
+    Ok(())
+}
+
+#[test]
+fn cancel() -> Result<()> {
+    init();
+
+    let module = r#"
+let aborted = false;
+
+export function spin() {
+    while (true) {}
+}
+
+export function wait() {
+    let abort = signal.current();
+    return new Promise(() => {
+        abort.addEventListener("abort", () => aborted = true);
+    });
+}
+
+export function check() {
+    return aborted;
+}
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, _guard) = machine.exec();
+
+    let spin  = handle.find("spin")?;
+    let wait  = handle.find("wait")?;
+    let check = handle.find("check")?;
+
+    let rx = spin.call(())?;
+    until(|| handle.queue_depth() == 0);
+    rx.cancel();
+
+    let rx = wait.call(())?;
+    until(|| handle.in_flight() == 1);
+    rx.cancel();
+    until(|| handle.in_flight() == 0);
+
+    assert_eq!(check.call(())?.recv()?, Value::from(true));
+
+    Ok(())
+}
+
+#[test]
+fn cancel_queued() -> Result<()> {
+    init();
+
+    let module = r#"
+let count = 0;
+
+export function wait() {
+    return new Promise(() => {});
+}
+
+export function bump() {
+    return ++count;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.in_flight(1);
+
+    let (handle, _guard) = machine.exec();
+
+    let wait = handle.find("wait")?;
+    let bump = handle.find("bump")?;
+
+    let rx = wait.call(())?;
+    until(|| handle.in_flight() == 1);
+
+    bump.call(())?.cancel();
+    until(|| handle.queue_depth() == 0);
+
+    rx.cancel();
+    until(|| handle.in_flight() == 0);
+
+    assert_eq!(bump.call(())?.recv()?, Value::from(1));
+
+    Ok(())
+}
+
+#[test]
+fn shutdown() -> Result<()> {
+    init();
+
+    let module = r#"
+export default async function test(a) {
+    return a + 1;
+}
+"#;
+
+    let runtime = Runtime::new()?;
+    runtime.block_on(async {
+        let machine = Machine::new(module.to_owned());
+        let (handle, guard) = machine.exec();
+
+        let function = handle.find_async("default").await?;
+        let result   = function.call_async(Value::from(1)).await?;
+        assert_eq!(result, Value::from(2));
+
+        let stats = handle.heap_stats_async().await?;
+        assert!(stats.used_heap_size() > 0);
+
+        guard.shutdown().await;
+
+        let error = handle.heap_stats_async().await.unwrap_err();
+        assert_eq!(error.to_string(), "machine terminated");
+
+        Ok(())
+    })
+}
+
+#[test]
+fn timeout() -> Result<()> {
+    init();
+
+    let module = r#"
+let resolve;
+
+export function wait() {
+    return new Promise(r => resolve = r);
+}
+
+export function wake(value) {
+    resolve(value);
+}
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, _guard) = machine.exec();
+
+    let wait = handle.find("wait")?;
+    let wake = handle.find("wake")?;
+
+    let mut rx = wait.call(())?;
+    assert_eq!(rx.try_recv()?, None);
+    assert_eq!(rx.recv_timeout(Duration::from_millis(50))?, None);
+
+    wake.call(Value::from(1))?.recv()?;
+
+    let result = rx.recv_timeout(Duration::from_secs(5))?;
+    assert_eq!(result, Some(Value::from(1)));
+    assert!(rx.try_recv().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn queue() -> Result<()> {
+    init();
+
+    let module = r#"
+export function spin() {
+    while (true) {}
+}
+
+export function echo(value) {
+    return value;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.queue(1);
+
+    let (handle, _guard) = machine.exec();
+
+    let spin = handle.find("spin")?;
+    let echo = handle.find("echo")?;
+
+    let rx = spin.call(())?;
+    until(|| handle.queue_depth() == 0);
+
+    let queued = echo.try_call(Value::from(1))?;
+    assert_eq!(handle.queue_depth(), 1);
+
+    let error = echo.try_call(Value::from(2)).err().unwrap();
+    assert_eq!(error.to_string(), "queue full");
+
+    rx.cancel();
+
+    assert_eq!(queued.recv()?, Value::from(1));
+    assert_eq!(handle.queue_depth(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn schedule() -> Result<()> {
+    init();
+
+    let module = r#"
+const order = [];
+
+export function spin() {
+    while (true) {}
+}
+
+export function push(value) {
+    order.push(value);
+}
+
+export function check() {
+    return order;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.weight("a".to_owned(), 2);
+
+    let (handle, _guard) = machine.exec();
+
+    let spin  = handle.find("spin")?;
+    let check = handle.find("check")?;
+
+    let push = |priority, tenant: &str| -> Result<_> {
+        let mut push = handle.find("push")?;
+        push.priority(priority);
+        push.tenant(tenant.to_owned());
+        Ok(push)
+    };
+
+    let low = push(Priority::Low, "")?;
+    let a   = push(Priority::Normal, "a")?;
+    let b   = push(Priority::Normal, "b")?;
+    let top = push(Priority::High, "")?;
+
+    let rx = spin.call(())?;
+    until(|| handle.queue_depth() == 0);
+
+    let mut queued = Vec::new();
+    queued.push(low.call(Value::from("low"))?);
+    for n in 1..=3 {
+        queued.push(a.call(Value::from(format!("a{n}")))?);
+    }
+    queued.push(b.call(Value::from("b1"))?);
+    queued.push(top.call(Value::from("top"))?);
+
+    rx.cancel();
+
+    for rx in queued {
+        rx.recv()?;
+    }
+
+    let order = check.call(())?.recv()?;
+    assert_eq!(order, serde_json::json!(["top", "a1", "a2", "b1", "a3", "low"]));
+
+    Ok(())
+}
+
+#[test]
+fn in_flight() -> Result<()> {
+    init();
+
+    let module = r#"
+export function wait() {
+    return new Promise(() => {});
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.in_flight(1);
+
+    let (handle, _guard) = machine.exec();
+    let wait = handle.find("wait")?;
+
+    let first  = wait.call(())?;
+    let second = wait.call(())?;
+
+    until(|| handle.in_flight() == 1);
+    assert_eq!(handle.queue_depth(), 1);
+
+    first.cancel();
+    until(|| handle.queue_depth() == 0 && handle.in_flight() == 1);
+
+    second.cancel();
+
+    Ok(())
+}
+
+#[test]
+fn supervise() -> Result<()> {
+    init();
+
+    let module = r#"
+export function wait() {
+    return new Promise(() => {});
+}
+
+export function crash() {
+    return new Map([[1, 2]]);
+}
+
+export function echo(value) {
+    return value;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.supervise(Backoff {
+        initial: Duration::from_millis(10),
+        max:     Duration::from_secs(1),
+    });
+
+    let (handle, _guard) = machine.exec();
+
+    let wait  = handle.find("wait")?;
+    let crash = handle.find("crash")?;
+    let echo  = handle.find("echo")?;
+
+    let pending = wait.call(())?;
+    assert!(crash.call(())?.recv().is_err());
+
+    let error = pending.recv().unwrap_err();
+    assert!(error.downcast_ref::<Restarted>().is_some());
+
+    assert_eq!(echo.call(Value::from(1))?.recv()?, Value::from(1));
+    assert_eq!(handle.health(), Health::Running);
+    assert_eq!(handle.restarts(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn ping() -> Result<()> {
+    init();
+
+    let module = r#"
+export function spin() {
+    while (true) {}
+}
+
+export function echo(value) {
+    return value;
+}
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, _guard) = machine.exec();
+
+    let spin = handle.find("spin")?;
+    let echo = handle.find("echo")?;
+
+    handle.ping(Duration::from_secs(5))?;
+    echo.call(Value::from(1))?.recv()?;
+
+    let status = handle.status();
+    assert_eq!(status.health, Health::Running);
+    assert_eq!(status.served, 1);
+    assert!(status.latency.is_some());
+    assert!(status.uptime > Duration::ZERO);
+
+    let rx = spin.call(())?;
+    until(|| handle.queue_depth() == 0);
+
+    let runtime = Runtime::new()?;
+    let error   = runtime.block_on(handle.ping_async(Duration::from_millis(50))).unwrap_err();
+    assert_eq!(error.to_string(), "ping timed out");
+
+    rx.cancel();
+    handle.ping(Duration::from_secs(5))?;
+
+    Ok(())
+}
+
+#[test]
+fn trace() -> Result<()> {
+    init();
+
+    let module = r#"
+export async function traced() {
+    let current = trace.current();
+    await fetch("http://localhost/");
+    let later = trace.current();
+    await fetch("http://localhost/");
+    return [current, later];
+}
+"#;
+
+    let traces = Arc::new(Mutex::new(Vec::new()));
+    let client = common::fetch::Capture::new(traces.clone());
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.extend(Fetch::new(client));
+
+    let (handle, _guard) = machine.exec();
+    let mut function = handle.find("traced")?;
+
+    let parent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    function.trace(Trace::parse(parent).unwrap());
+
+    let result = function.call(())?.recv()?;
+    assert_eq!(result[0]["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_ne!(result[0]["spanId"], "00f067aa0ba902b7");
+    assert_eq!(result[0], result[1]);
+
+    let traceparent = result[0]["traceparent"].as_str().map(String::from);
+    assert_eq!(*traces.lock().unwrap(), vec![traceparent.clone(), traceparent]);
+
+    Ok(())
+}
+
+#[test]
+fn realm() -> Result<()> {
+    init();
+
+    let module = r#"
+let count = 0;
+
+export function bump() {
+    return ++count;
+}
+
+export function kind() {
+    return typeof fetch;
+}
+
+export async function get() {
+    let response = await fetch("http://localhost/");
+    return response instanceof Response;
+}
+"#;
+
+    let traces = Arc::new(Mutex::new(Vec::new()));
+    let client = common::fetch::Capture::new(traces);
+
+    let mut fetching = Realm::new(module.to_owned());
+    fetching.extend(Fetch::new(client));
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.realm("a".to_owned(), Realm::new(module.to_owned()));
+    machine.realm("b".to_owned(), fetching);
+
+    let (handle, _guard) = machine.exec();
+
+    let bump = handle.find("bump")?;
+    bump.call(())?.recv()?;
+    bump.call(())?.recv()?;
+
+    let bump = handle.find_in("a", "bump")?;
+    assert_eq!(bump.call(())?.recv()?, Value::from(1));
+
+    let kind = handle.find("kind")?;
+    assert_eq!(kind.call(())?.recv()?, Value::from("undefined"));
+
+    let kind = handle.find_in("b", "kind")?;
+    assert_eq!(kind.call(())?.recv()?, Value::from("function"));
+
+    let get = handle.find_in("b", "get")?;
+    assert_eq!(get.call(())?.recv()?, Value::from(true));
+
+    let error = handle.find_in("c", "bump").err().unwrap();
+    assert_eq!(error.to_string(), "c is not a realm");
+
+    Ok(())
+}
+
+#[test]
+fn pristine() -> Result<()> {
+    init();
+
+    let module = r#"
+let count = 0;
+
+export function bump() {
+    globalThis.total = (globalThis.total ?? 0) + 1;
+    return [++count, globalThis.total];
+}
+
+export async function later() {
+    await null;
+    return ++count;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.pristine(true);
+
+    let (handle, _guard) = machine.exec();
+    let bump  = handle.find("bump")?;
+    let later = handle.find("later")?;
+
+    for _ in 0..3 {
+        assert_eq!(bump.call(())?.recv()?, serde_json::json!([1, 1]));
+        assert_eq!(later.call(())?.recv()?, Value::from(1));
+    }
+
+    // fresh contexts reuse the code cache rather than compile the source
+    handle.ping(Duration::from_secs(5))?;
+    assert_eq!(handle.status().compiles, 1);
+
+    Ok(())
+}
+
+#[test]
+fn reload() -> Result<()> {
+    init();
+
+    let machine = Machine::new("export function version() { return 1; }".to_owned());
+    let (handle, _guard) = machine.exec();
+
+    let version = handle.find("version")?;
+    assert_eq!(version.call(())?.recv()?, Value::from(1));
+
+    let module = r#"
+export function version() {
+    return 2;
+}
+
+export const extra = () => 3;
+"#;
+
+    handle.reload(module.to_owned())?;
+    assert_eq!(version.call(())?.recv()?, Value::from(2));
+
+    let extra = handle.find("extra")?;
+    assert_eq!(extra.call(())?.recv()?, Value::from(3));
+
+    let error = handle.reload("export function version(".to_owned()).unwrap_err();
+    assert!(error.downcast_ref::<Failure>().is_some());
+    assert_eq!(version.call(())?.recv()?, Value::from(2));
+
+    let module = r#"
+export function version() {
+    return 3;
+}
+
+throw new Error("broken");
+"#;
+
+    let error = handle.reload(module.to_owned()).unwrap_err();
+    assert!(error.downcast_ref::<Failure>().is_some());
+    assert_eq!(version.call(())?.recv()?, Value::from(2));
+
+    Ok(())
+}
+
+#[test]
+fn reload_drain() -> Result<()> {
+    init();
+
+    let module = r#"
+export function version() {
+    return 1;
+}
+
+export function wait() {
+    return new Promise(() => {});
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.drain(Duration::from_millis(50));
+
+    let (handle, _guard) = machine.exec();
+    let version = handle.find("version")?;
+    let wait    = handle.find("wait")?;
+
+    let rx = wait.call(())?;
+    until(|| handle.in_flight() == 1);
+
+    let error = handle.reload("export function version() { return 2; }".to_owned()).unwrap_err();
+    assert!(error.to_string().contains("timed out"));
+    assert_eq!(version.call(())?.recv()?, Value::from(1));
+
+    rx.cancel();
+    until(|| handle.in_flight() == 0);
+
+    Ok(())
+}
+
+#[test]
+fn exports() -> Result<()> {
+    init();
+
+    let module = r#"
+export const schema = { type: "object", required: ["a"] };
+
+export function plain(a, b) {}
+
+export async function later(value) {}
+
+export function* items() {}
+
+export default function named() {}
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, _guard) = machine.exec();
+
+    let entry = |name: &str, kind, function: Option<&str>, length| Entry {
+        name:     name.to_owned(),
+        kind:     kind,
+        function: function.map(String::from),
+        length:   length,
+    };
+
+    assert_eq!(handle.exports()?, vec![
+        entry("default", Kind::Function,      Some("named"), Some(0)),
+        entry("items",   Kind::Generator,     Some("items"), Some(0)),
+        entry("later",   Kind::AsyncFunction, Some("later"), Some(1)),
+        entry("plain",   Kind::Function,      Some("plain"), Some(2)),
+        entry("schema",  Kind::Value,         None,          None),
+    ]);
+
+    let schema = handle.export("schema")?;
+    assert_eq!(schema, serde_json::json!({ "type": "object", "required": ["a"] }));
+
+    let error = handle.export("plain").unwrap_err();
+    assert_eq!(error.to_string(), "plain is a function");
+
+    let error = handle.export("missing").unwrap_err();
+    assert_eq!(error.to_string(), "missing is not exported");
+
+    Ok(())
+}
+
+#[test]
+fn globals() -> Result<()> {
+    init();
+
+    let module = r#"
+globalThis.count = limit.start;
+
+export function bump() {
+    return ++count;
+}
+
+export function clear() {
+    limit = null;
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.global("limit".to_owned(), serde_json::json!({ "start": 10 }));
+
+    let (handle, _guard) = machine.exec();
+    let bump = handle.find("bump")?;
+
+    assert_eq!(bump.call(())?.recv()?, Value::from(11));
+    assert_eq!(handle.get_global("count")?, Value::from(11));
+
+    handle.set_global("count", Value::from(20))?;
+    assert_eq!(bump.call(())?.recv()?, Value::from(21));
+
+    handle.set_global("config", serde_json::json!({ "debug": true }))?;
+    assert_eq!(handle.get_global("config")?, serde_json::json!({ "debug": true }));
+
+    let clear = handle.find("clear")?;
+    assert!(clear.call(())?.recv().is_err());
+
+    let error = handle.set_global("limit", Value::from(0)).unwrap_err();
+    assert_eq!(error.to_string(), "limit is read-only");
+    assert_eq!(handle.get_global("limit")?, serde_json::json!({ "start": 10 }));
+
+    let error = handle.get_global("missing").unwrap_err();
+    assert_eq!(error.to_string(), "missing is not defined");
+
+    Ok(())
+}
+
+#[test]
+fn pristine_globals() -> Result<()> {
+    init();
+
+    let module = r#"
+export function read() {
+    return [globalThis.limit, globalThis.step ?? null];
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.global("limit".to_owned(), Value::from(1));
+    machine.pristine(true);
+
+    let (handle, _guard) = machine.exec();
+    let read = handle.find("read")?;
+
+    assert_eq!(read.call(())?.recv()?, serde_json::json!([1, null]));
+
+    handle.set_global("step", Value::from(2))?;
+    assert_eq!(read.call(())?.recv()?, serde_json::json!([1, 2]));
+    assert_eq!(handle.get_global("step")?, Value::from(2));
+
+    Ok(())
+}
+
+#[test]
+fn eval() -> Result<()> {
+    init();
+
+    let module = r#"
+globalThis.count = 2;
+
+export function bump() {
+    return ++count;
+}
+"#;
+
+    let machine = Machine::new(module.to_owned());
+    let (handle, _guard) = machine.exec();
+
+    assert_eq!(handle.eval("count * 21")?, Value::from(42));
+    assert_eq!(handle.eval("var added = { a: [1, 2] }; added")?, serde_json::json!({ "a": [1, 2] }));
+    assert_eq!(handle.get_global("added")?, serde_json::json!({ "a": [1, 2] }));
+    assert_eq!(handle.eval("(async () => count + 1)()")?, Value::from(3));
+
+    let error   = handle.eval("throw new Error('boom')").unwrap_err();
+    let failure = error.downcast_ref::<Failure>().unwrap();
+    assert!(failure.message.contains("boom"));
+
+    let error = handle.eval("1 +").unwrap_err();
+    assert!(error.downcast_ref::<Failure>().is_some());
+
+    let error   = handle.eval("Promise.reject(new Error('later'))").unwrap_err();
+    let failure = error.downcast_ref::<Failure>().unwrap();
+    assert_eq!(failure.message, "Error: later");
+
+    Ok(())
+}
+
+#[test]
+fn heap_limit() -> Result<()> {
+    init();
+
+    let module = r#"
+export function grow() {
+    let chunks = [];
+    while (true) {
+        chunks.push(new Array(1024 * 1024).fill(1));
+    }
+}
+"#;
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.heap_limit(64 * 1024 * 1024);
+
+    let (handle, _guard) = machine.exec();
+    let grow = handle.find("grow")?;
+
+    let error = grow.call(())?.recv().unwrap_err();
+    assert_eq!(error.to_string(), "heap limit reached");
+    until(|| handle.health() == Health::Failed);
+
+    Ok(())
+}
+
+#[test]
+fn eval_cancel() -> Result<()> {
+    init();
+
+    let machine = Machine::new("export function noop() {}".to_owned());
+    let (handle, _guard) = machine.exec();
+
+    // dropping the pending eval terminates the runaway script
+    let runtime = Runtime::new()?;
+    let result  = runtime.block_on(async {
+        let expiry = Duration::from_millis(50);
+        tokio::time::timeout(expiry, handle.eval_async("while (true) {}")).await
+    });
+    assert!(result.is_err());
+
+    assert_eq!(handle.eval("1 + 1")?, Value::from(2));
+
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn metrics() -> Result<()> {
+    use v8vm::vm::Prometheus;
+
+    init();
+
+    let module = r#"
+export function echo(value) {
+    return value;
+}
+
+export async function fail() {
+    throw new Error("failure");
+}
+"#;
+
+    let recorder = Prometheus::new();
+
+    let mut machine = Machine::new(module.to_owned());
+    machine.name("test.js".to_owned());
+    machine.metrics(recorder.clone());
+
+    let (handle, _guard) = machine.exec();
+    let echo = handle.find("echo")?;
+    let fail = handle.find("fail")?;
+
+    echo.call(Value::from(1))?.recv()?;
+    echo.call(Value::from(2))?.recv()?;
+    fail.call(())?.recv().unwrap_err();
+
+    let text = recorder.render();
+    assert!(text.contains(r#"v8vm_calls_total{script="test.js",export="echo",outcome="success"} 2"#));
+    assert!(text.contains(r#"v8vm_calls_total{script="test.js",export="fail",outcome="error"} 1"#));
+    assert!(text.contains(r#"v8vm_promise_settle_seconds_count{script="test.js",export="fail"} 1"#));
+    assert!(text.contains(r#"v8vm_queue_wait_seconds_count{script="test.js"} 3"#));
+    assert!(text.contains(r#"v8vm_queue_depth{script="test.js"} 0"#));
+    assert!(text.contains(r#"v8vm_heap_used_bytes{script="test.js"}"#));
+
+    Ok(())
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn repl() -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_v8vm"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let input = "const x = await Promise.resolve(1)\nx\n";
+    child.stdin.take().unwrap().write_all(input.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    let output = String::from_utf8(output.stdout)?;
+    assert_eq!(output.lines().collect::<Vec<_>>(), vec!["null", "1"]);
+
+    Ok(())
+}
+
 impl Default for Test {
     fn default() -> Self {
         Self {